@@ -0,0 +1,335 @@
+//! Este módulo implementa o suporte a APIC/ACPI, substituindo o PIC 8259 legado
+//! como fonte de interrupções de hardware e do timer do scheduler.
+
+use crate::memory;
+use x86_64::{
+    structures::paging::{Mapper, Page, PageTableFlags, Size4KiB},
+    PhysAddr, VirtAddr,
+};
+
+/// Endereço físico padrão do MMIO do Local APIC.
+const LAPIC_PHYS_BASE: u64 = 0xFEE0_0000;
+
+/// Offset do registrador de ID do Local APIC.
+const REG_ID: usize = 0x20;
+/// Offset do registrador de Spurious Interrupt Vector (SVR).
+const REG_SVR: usize = 0xF0;
+/// Offset do registrador de End-of-Interrupt (EOI).
+const REG_EOI: usize = 0xB0;
+/// Offset do registrador de Local Vector Table para o timer.
+const REG_LVT_TIMER: usize = 0x320;
+/// Offset do registrador de configuração de divisor do timer.
+const REG_TIMER_DIV: usize = 0x3E0;
+/// Offset do registrador de contagem inicial do timer.
+const REG_TIMER_INITIAL_COUNT: usize = 0x380;
+
+/// Vetor usado para a interrupção espúria.
+const SPURIOUS_VECTOR: u8 = 0xFF;
+/// Vetor do timer do Local APIC, reaproveitado pelo scheduler.
+pub const TIMER_VECTOR: u8 = 0x20;
+/// Vetor do teclado, roteado através do I/O APIC.
+pub const KEYBOARD_VECTOR: u8 = 0x21;
+/// Vetor de software usado por uma tarefa para ceder a CPU voluntariamente
+/// (`yield_now`, `receive`, `send`, `sleep`), sem passar pelo Local APIC.
+///
+/// É um vetor próprio, distinto de `TIMER_VECTOR`, para que só a interrupção
+/// de hardware do timer avance `TICKS`: se um yield voluntário reaproveitasse
+/// o vetor do timer, um executor girando em `yield_now()` inflaria o relógio
+/// global arbitrariamente rápido e venceria `sleep`/`receive_timeout`
+/// prematuramente.
+pub const YIELD_VECTOR: u8 = 0x22;
+
+/// Abstração mínima sobre o MMIO do Local APIC.
+///
+/// Todos os registradores são alinhados a 16 bytes, então cada acesso é feito
+/// como um `u32` na posição `base + offset`.
+pub struct LocalApic {
+    base: VirtAddr,
+}
+
+impl LocalApic {
+    /// Lê um registrador do Local APIC.
+    unsafe fn read(&self, offset: usize) -> u32 {
+        core::ptr::read_volatile((self.base.as_u64() as usize + offset) as *const u32)
+    }
+
+    /// Escreve em um registrador do Local APIC.
+    unsafe fn write(&self, offset: usize, value: u32) {
+        core::ptr::write_volatile((self.base.as_u64() as usize + offset) as *mut u32, value);
+    }
+
+    /// Lê o ID do Local APIC, usado pelo scheduler como `CpuId` da CPU atual.
+    ///
+    /// Em modo xAPIC, o ID ocupa os bits 24-31 do registrador.
+    pub fn id(&self) -> u32 {
+        unsafe { self.read(REG_ID) >> 24 }
+    }
+
+    /// Habilita o Local APIC escrevendo no Spurious Interrupt Vector Register.
+    ///
+    /// O bit 8 precisa estar setado para que o APIC entre em funcionamento;
+    /// o vetor espúrio evita que interrupções perdidas caiam em um handler real.
+    fn enable(&self) {
+        unsafe {
+            self.write(REG_SVR, (1 << 8) | SPURIOUS_VECTOR as u32);
+        }
+    }
+
+    /// Programa o timer do Local APIC em modo periódico.
+    fn start_periodic_timer(&self, initial_count: u32) {
+        unsafe {
+            // Divide o clock do barramento por 16.
+            self.write(REG_TIMER_DIV, 0x3);
+            // Bit 17 = modo periódico.
+            self.write(REG_LVT_TIMER, (1 << 17) | TIMER_VECTOR as u32);
+            self.write(REG_TIMER_INITIAL_COUNT, initial_count);
+        }
+    }
+
+    /// Sinaliza o fim de uma interrupção escrevendo 0 no registrador de EOI.
+    pub fn end_of_interrupt(&self) {
+        unsafe {
+            self.write(REG_EOI, 0);
+        }
+    }
+}
+
+/// O I/O APIC, cuja tabela de redirecionamento é usada para rotear IRQs
+/// físicas (como o teclado) para um vetor da IDT.
+pub struct IoApic {
+    base: VirtAddr,
+}
+
+impl IoApic {
+    unsafe fn write_register(&self, reg: u8, value: u32) {
+        let regsel = self.base.as_u64() as *mut u32;
+        let win = (self.base.as_u64() + 0x10) as *mut u32;
+        core::ptr::write_volatile(regsel, reg as u32);
+        core::ptr::write_volatile(win, value);
+    }
+
+    /// Roteia uma IRQ física para o vetor da IDT informado, na CPU atual.
+    pub fn route_irq(&self, irq: u8, vector: u8) {
+        let redirection_table_reg = 0x10 + irq * 2;
+        unsafe {
+            self.write_register(redirection_table_reg, vector as u32);
+            self.write_register(redirection_table_reg + 1, 0);
+        }
+    }
+}
+
+/// Resultado da descoberta da MADT: os endereços físicos do Local APIC e do
+/// primeiro I/O APIC encontrados.
+struct MadtInfo {
+    local_apic_phys: u64,
+    io_apic_phys: u64,
+}
+
+/// Lê um valor de tipo `T` a partir de um endereço físico, traduzindo-o para
+/// virtual via `memory::phys_to_virt` antes de desreferenciar.
+///
+/// Usado por `parse_madt`/`find_table_in_sdt`, que só têm endereços físicos
+/// em mãos (vindos do RSDP ou de ponteiros de tabela ACPI); em um kernel
+/// mapeado por offset, desreferenciar esses endereços diretamente leria
+/// memória errada (ou causaria page fault).
+unsafe fn read_phys<T: Copy>(phys: u64) -> T {
+    memory::phys_to_virt(PhysAddr::new(phys)).as_ptr::<T>().read()
+}
+
+/// Procura a assinatura de 8 bytes `"RSD PTR "` em `phys_range`, alinhada a
+/// 16 bytes, e retorna o endereço virtual de seu início.
+unsafe fn scan_for_rsdp(phys_range: core::ops::Range<u64>) -> Option<VirtAddr> {
+    let mut phys = phys_range.start;
+    while phys < phys_range.end {
+        let virt = memory::phys_to_virt(PhysAddr::new(phys));
+        let signature = core::slice::from_raw_parts(virt.as_ptr::<u8>(), 8);
+        if signature == b"RSD PTR " {
+            return Some(virt);
+        }
+        phys += 16;
+    }
+    None
+}
+
+/// Localiza o RSDP varrendo a EBDA e a região `0xE0000..0x100000`, onde o
+/// BIOS o deixa em qualquer PC compatível.
+///
+/// `bootloader` (crate usado por este kernel) mapeia esse intervalo
+/// linearmente no offset física-para-virtual de `memory::init`, então basta
+/// traduzir os endereços físicos candidatos via `phys_to_virt`; não há
+/// necessidade de receber o endereço do bootloader.
+///
+/// # Safety
+///
+/// Só deve ser chamada depois de `memory::init`.
+pub unsafe fn find_rsdp() -> Option<VirtAddr> {
+    // O segmento da EBDA (Extended BIOS Data Area) fica no word em 0x40E,
+    // como um endereço físico dividido por 16.
+    let ebda_segment: u16 = read_phys(0x40E);
+    let ebda_start = (ebda_segment as u64) << 4;
+    if ebda_start != 0 {
+        if let Some(rsdp) = scan_for_rsdp(ebda_start..ebda_start + 1024) {
+            return Some(rsdp);
+        }
+    }
+
+    scan_for_rsdp(0xE0000..0x100000)
+}
+
+/// A partir do RSDP em `rsdp_addr`, percorre RSDT/XSDT até a MADT e extrai os endereços do
+/// Local APIC e do I/O APIC.
+///
+/// # Safety
+///
+/// O chamador deve garantir que `rsdp_addr` aponta para uma estrutura RSDP
+/// válida, acessível através do mapeamento de memória física-para-virtual.
+unsafe fn parse_madt(rsdp_addr: VirtAddr) -> MadtInfo {
+    // Layout simplificado: assume ACPI 1.0 (RSDT de 32 bits) por padrão e cai
+    // para o XSDT apenas se a revisão indicar ACPI 2.0+.
+    let revision = *((rsdp_addr.as_u64() + 15) as *const u8);
+
+    let madt_phys = if revision >= 2 {
+        let xsdt_addr = *((rsdp_addr.as_u64() + 24) as *const u64);
+        find_table_in_sdt(xsdt_addr, true)
+    } else {
+        let rsdt_addr = *((rsdp_addr.as_u64() + 16) as *const u32) as u64;
+        find_table_in_sdt(rsdt_addr, false)
+    };
+
+    // Valores padrão caso a MADT não informe overrides: LAPIC em 0xFEE00000 e
+    // o primeiro I/O APIC em 0xFEC00000, que é o padrão de fato na maioria dos chipsets.
+    let mut info = MadtInfo {
+        local_apic_phys: LAPIC_PHYS_BASE,
+        io_apic_phys: 0xFEC0_0000,
+    };
+
+    if madt_phys != 0 {
+        // offset 0x24 da MADT: endereço de 32 bits do Local APIC.
+        info.local_apic_phys = read_phys::<u32>(madt_phys + 0x24) as u64;
+        // As entradas variáveis começam em offset 0x2C; procuramos a primeira
+        // entrada do tipo 1 (I/O APIC), que traz seu endereço físico em +4.
+        let entries_start = madt_phys + 0x2C;
+        let length = read_phys::<u32>(madt_phys + 4) as u64;
+        let mut cursor = entries_start;
+        while cursor < madt_phys + length {
+            let entry_type = read_phys::<u8>(cursor);
+            let entry_len = read_phys::<u8>(cursor + 1) as u64;
+            if entry_type == 1 {
+                info.io_apic_phys = read_phys::<u32>(cursor + 4) as u64;
+                break;
+            }
+            cursor += entry_len.max(2);
+        }
+    }
+
+    info
+}
+
+/// Varre as entradas do RSDT/XSDT procurando a assinatura "APIC" (MADT).
+///
+/// Retorna o endereço físico da MADT, ou 0 se não encontrada.
+unsafe fn find_table_in_sdt(sdt_phys: u64, is_xsdt: bool) -> u64 {
+    if sdt_phys == 0 {
+        return 0;
+    }
+    let length = read_phys::<u32>(sdt_phys + 4) as u64;
+    let entries_start = sdt_phys + 36;
+    let entry_count = if is_xsdt {
+        (length - 36) / 8
+    } else {
+        (length - 36) / 4
+    };
+
+    for i in 0..entry_count {
+        let table_phys = if is_xsdt {
+            read_phys::<u64>(entries_start + i * 8)
+        } else {
+            read_phys::<u32>(entries_start + i * 4) as u64
+        };
+        let signature_virt = memory::phys_to_virt(PhysAddr::new(table_phys));
+        let signature = core::slice::from_raw_parts(signature_virt.as_ptr::<u8>(), 4);
+        if signature == b"APIC" {
+            return table_phys;
+        }
+    }
+    0
+}
+
+/// Mascara todas as IRQs nos dois PICs 8259, escrevendo 0xFF nas portas de dados.
+///
+/// Isso impede que o PIC legado continue entregando interrupções depois que
+/// o APIC assume o controle.
+fn mask_legacy_pics() {
+    use x86_64::instructions::port::Port;
+    unsafe {
+        let mut pic1_data: Port<u8> = Port::new(0x21);
+        let mut pic2_data: Port<u8> = Port::new(0xA1);
+        pic1_data.write(0xFFu8);
+        pic2_data.write(0xFFu8);
+    }
+}
+
+/// Inicializa APIC/ACPI como substituto do PIC 8259 legado.
+///
+/// Mascara os PICs, mapeia o MMIO do Local APIC e do I/O APIC (como
+/// não-cacheável/write-through), habilita o Local APIC, programa o timer em
+/// modo periódico e roteia a IRQ do teclado através do I/O APIC.
+///
+/// # Safety
+///
+/// `rsdp_addr` deve ser um endereço virtual válido para a estrutura RSDP
+/// (tipicamente obtido via `find_rsdp`), e `mapper`/`frame_allocator` devem
+/// estar aptos a mapear páginas adicionais.
+pub unsafe fn init(
+    rsdp_addr: VirtAddr,
+    mapper: &mut impl Mapper<Size4KiB>,
+    frame_allocator: &mut impl x86_64::structures::paging::FrameAllocator<Size4KiB>,
+) -> (LocalApic, IoApic) {
+    mask_legacy_pics();
+
+    let madt = parse_madt(rsdp_addr);
+
+    let lapic_base = map_mmio_page(madt.local_apic_phys, mapper, frame_allocator);
+    let ioapic_base = map_mmio_page(madt.io_apic_phys, mapper, frame_allocator);
+
+    let lapic = LocalApic { base: lapic_base };
+    let ioapic = IoApic { base: ioapic_base };
+
+    lapic.enable();
+    // Valor de contagem inicial escolhido para uma cadência de scheduler
+    // comparável ao PIT anterior (~100Hz); ajustável conforme calibração do bus clock.
+    lapic.start_periodic_timer(0x10_0000);
+    ioapic.route_irq(1, KEYBOARD_VECTOR);
+
+    (lapic, ioapic)
+}
+
+/// Mapeia uma página de MMIO de 4KiB com as flags no-cache/write-through e
+/// retorna seu endereço virtual.
+///
+/// A página em `phys_to_virt(phys_addr)` já está presente, pois cai dentro
+/// da janela de mapeamento linear física-para-virtual que o bootloader
+/// instala (que precisa cobrir `0xFEE00xxx`/`0xFEC00xxx`); por isso apenas
+/// atualizamos as flags da entrada existente em vez de tentar um `map_to`,
+/// que falharia com `PageAlreadyMapped`.
+unsafe fn map_mmio_page(
+    phys_addr: u64,
+    mapper: &mut impl Mapper<Size4KiB>,
+    _frame_allocator: &mut impl x86_64::structures::paging::FrameAllocator<Size4KiB>,
+) -> VirtAddr {
+    // Identity-map simplificado: usa o próprio endereço físico deslocado pelo
+    // offset de memória física-para-virtual já mapeado pelo `mem` module.
+    let virt = memory::phys_to_virt(PhysAddr::new(phys_addr));
+    let page: Page<Size4KiB> = Page::containing_address(virt);
+    let flags = PageTableFlags::PRESENT
+        | PageTableFlags::WRITABLE
+        | PageTableFlags::NO_CACHE
+        | PageTableFlags::WRITE_THROUGH;
+
+    mapper
+        .update_flags(page, flags)
+        .expect("falha ao atualizar flags da página de MMIO do APIC")
+        .flush();
+
+    virt
+}
@@ -0,0 +1,190 @@
+//! Este módulo implementa um executor assíncrono cooperativo, que roda sobre
+//! o scheduler preemptivo de `task` como uma única `Task`.
+//!
+//! O executor em si não introduz nenhum novo mecanismo de troca de contexto:
+//! ele apenas faz o poll de futures dentro do `entry_point` de uma `Task`
+//! normal, cedendo a CPU com `task::yield_now` quando não há nada pronto.
+
+use alloc::boxed::Box;
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::sync::Arc;
+use alloc::task::Wake;
+use core::future::Future;
+use core::pin::Pin;
+use core::sync::atomic::{AtomicU64, Ordering};
+use core::task::{Context, Poll, Waker};
+use spin::Mutex;
+
+use crate::task::{self, Message};
+
+/// Identificador único de uma tarefa assíncrona dentro de um `Executor`.
+///
+/// Independente de `task::TaskId`: todas as tarefas assíncronas de um
+/// `Executor` compartilham a mesma `task::Task` do scheduler, que é quem
+/// hospeda o executor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct AsyncTaskId(u64);
+
+impl AsyncTaskId {
+    fn new() -> Self {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+        AsyncTaskId(NEXT_ID.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+/// Uma tarefa assíncrona: uma future de tipo apagado, pinada no heap.
+struct AsyncTask {
+    id: AsyncTaskId,
+    future: Pin<Box<dyn Future<Output = ()>>>,
+}
+
+impl AsyncTask {
+    fn new(future: impl Future<Output = ()> + 'static) -> Self {
+        AsyncTask {
+            id: AsyncTaskId::new(),
+            future: Box::pin(future),
+        }
+    }
+
+    fn poll(&mut self, context: &mut Context) -> Poll<()> {
+        self.future.as_mut().poll(context)
+    }
+}
+
+/// `Waker` que, ao ser acordado, reenfileira o id da tarefa assíncrona na
+/// fila de prontas do executor.
+struct TaskWaker {
+    task_id: AsyncTaskId,
+    task_queue: Arc<Mutex<VecDeque<AsyncTaskId>>>,
+}
+
+impl TaskWaker {
+    fn wake_task(&self) {
+        self.task_queue.lock().push_back(self.task_id);
+    }
+}
+
+impl Wake for TaskWaker {
+    fn wake(self: Arc<Self>) {
+        self.wake_task();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.wake_task();
+    }
+}
+
+/// Executor cooperativo de futures.
+///
+/// Mantém uma fila de ids de tarefas prontas e um mapa de tarefas
+/// assíncronas; cada `Waker` passado a `poll` empurra o id da tarefa de
+/// volta na fila quando a tarefa é acordada.
+pub struct Executor {
+    tasks: BTreeMap<AsyncTaskId, AsyncTask>,
+    task_queue: Arc<Mutex<VecDeque<AsyncTaskId>>>,
+    waker_cache: BTreeMap<AsyncTaskId, Waker>,
+}
+
+impl Executor {
+    /// Cria um `Executor` vazio.
+    pub fn new() -> Self {
+        Executor {
+            tasks: BTreeMap::new(),
+            task_queue: Arc::new(Mutex::new(VecDeque::new())),
+            waker_cache: BTreeMap::new(),
+        }
+    }
+
+    /// Agenda uma nova future para ser executada pelo executor.
+    pub fn spawn(&mut self, future: impl Future<Output = ()> + 'static) {
+        let task = AsyncTask::new(future);
+        let task_id = task.id;
+        if self.tasks.insert(task_id, task).is_some() {
+            panic!("id de tarefa assíncrona duplicado");
+        }
+        self.task_queue.lock().push_back(task_id);
+    }
+
+    /// Faz o poll de cada tarefa atualmente na fila de prontas.
+    fn run_ready_tasks(&mut self) {
+        while let Some(task_id) = self.task_queue.lock().pop_front() {
+            let task = match self.tasks.get_mut(&task_id) {
+                Some(task) => task,
+                None => continue, // já concluída em uma iteração anterior
+            };
+
+            let task_queue = self.task_queue.clone();
+            let waker = self.waker_cache.entry(task_id).or_insert_with(|| {
+                Waker::from(Arc::new(TaskWaker {
+                    task_id,
+                    task_queue,
+                }))
+            });
+
+            let mut context = Context::from_waker(waker);
+            match task.poll(&mut context) {
+                Poll::Ready(()) => {
+                    self.tasks.remove(&task_id);
+                    self.waker_cache.remove(&task_id);
+                }
+                Poll::Pending => {}
+            }
+        }
+    }
+
+    /// Ponto de entrada do executor, pensado para ser o `entry_point` de uma
+    /// `task::Task` registrada no `Scheduler`.
+    ///
+    /// Faz o poll de tudo que está pronto e cede a CPU com
+    /// `task::yield_now` enquanto a fila de prontas estiver vazia, em vez de
+    /// girar em busy-wait.
+    pub fn run(&mut self) -> ! {
+        loop {
+            self.run_ready_tasks();
+            if self.task_queue.lock().is_empty() {
+                task::yield_now();
+            }
+        }
+    }
+}
+
+/// Future retornada por `recv`: aguarda a próxima mensagem da mailbox da
+/// tarefa do scheduler que hospeda o executor.
+pub struct Receive {
+    owner: task::TaskId,
+}
+
+impl Future for Receive {
+    type Output = Message;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Message> {
+        match task::try_receive_for(self.owner) {
+            Some(message) => Poll::Ready(message),
+            None => {
+                task::register_mailbox_waker(self.owner, cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+/// Aguarda assincronamente a próxima mensagem da mailbox da tarefa atual.
+///
+/// Ao contrário de `task::receive`, não bloqueia a CPU: registra o waker da
+/// future atual na mailbox e devolve o controle ao executor, que poderá
+/// rodar outras futures até que `task::send` acorde esta.
+pub fn recv() -> Receive {
+    Receive {
+        owner: task::current_scheduler().lock().current_task_id(),
+    }
+}
+
+/// Cria um `Executor` vazio e o roda.
+///
+/// Serve como `entry_point` de uma `task::Task`, para que o executor
+/// assíncrono rode como mais uma tarefa preemptiva do scheduler, ao lado do
+/// shell.
+pub fn run_executor() -> ! {
+    let mut executor = Executor::new();
+    executor.run();
+}
@@ -0,0 +1,68 @@
+//! Este módulo configura a GDT (Global Descriptor Table) e a TSS (Task State
+//! Segment) usadas pelo kernel, principalmente para fornecer uma pilha
+//! separada (via IST) ao handler de double fault em `interrupts.rs`.
+//!
+//! Um double fault disparado por um estouro de pilha (a causa mais comum em
+//! um kernel bare-metal) não pode ser tratado na própria pilha que estourou;
+//! a CPU troca para a pilha do IST indicada em `DOUBLE_FAULT_IST_INDEX`
+//! antes mesmo de empilhar o stack frame da interrupção.
+
+use lazy_static::lazy_static;
+use x86_64::instructions::segmentation::{Segment, CS};
+use x86_64::instructions::tables::load_tss;
+use x86_64::structures::gdt::{Descriptor, GlobalDescriptorTable, SegmentSelector};
+use x86_64::structures::tss::TaskStateSegment;
+use x86_64::VirtAddr;
+
+/// Índice, na IST da TSS, da pilha reservada para o handler de double fault.
+pub const DOUBLE_FAULT_IST_INDEX: u16 = 0;
+
+/// Tamanho da pilha de double fault.
+const STACK_SIZE: usize = 4096 * 5;
+
+lazy_static! {
+    /// A TSS do kernel, com a pilha do IST usada pelo double fault.
+    static ref TSS: TaskStateSegment = {
+        let mut tss = TaskStateSegment::new();
+        tss.interrupt_stack_table[DOUBLE_FAULT_IST_INDEX as usize] = {
+            // `static mut` em vez de alocar no heap: a GDT é carregada antes
+            // do heap existir.
+            static mut STACK: [u8; STACK_SIZE] = [0; STACK_SIZE];
+
+            let stack_start = VirtAddr::from_ptr(core::ptr::addr_of!(STACK));
+            stack_start + STACK_SIZE as u64
+        };
+        tss
+    };
+}
+
+lazy_static! {
+    /// A GDT do kernel: apenas os descritores mínimos exigidos pelo modo
+    /// longo (um segmento de código) mais o seletor da TSS.
+    static ref GDT: (GlobalDescriptorTable, Selectors) = {
+        let mut gdt = GlobalDescriptorTable::new();
+        let code_selector = gdt.add_entry(Descriptor::kernel_code_segment());
+        let tss_selector = gdt.add_entry(Descriptor::tss_segment(&TSS));
+        (gdt, Selectors { code_selector, tss_selector })
+    };
+}
+
+/// Os seletores de segmento instalados em `GDT`, guardados para serem
+/// carregados em `CS`/`load_tss` por `init`.
+struct Selectors {
+    code_selector: SegmentSelector,
+    tss_selector: SegmentSelector,
+}
+
+/// Carrega a GDT e a TSS do kernel.
+///
+/// Deve ser chamada antes de `interrupts::init_idt`, já que o handler de
+/// double fault depende de `DOUBLE_FAULT_IST_INDEX` já estar configurado na
+/// TSS carregada.
+pub fn init() {
+    GDT.0.load();
+    unsafe {
+        CS::set_reg(GDT.1.code_selector);
+        load_tss(GDT.1.tss_selector);
+    }
+}
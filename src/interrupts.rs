@@ -1,9 +1,8 @@
 //! Este módulo lida com o tratamento de interrupções e exceções da CPU.
 
+use crate::apic;
 use crate::{print, println};
 use lazy_static::lazy_static;
-use pc_keyboard::{layouts, DecodedKey, HandleControl, Keyboard, ScancodeSet1};
-use pic8259::ChainedPics;
 use spin;
 use x86_64::structures::idt::{InterruptDescriptorTable, InterruptStackFrame, PageFaultErrorCode};
 use core::convert::From;
@@ -11,25 +10,24 @@ use core::result::Result::Ok;
 use core::option::Option::Some;
 use core::panic;
 
-/// O offset inicial para as interrupções do PIC primário.
-pub const PIC_1_OFFSET: u8 = 32;
-/// O offset inicial para as interrupções do PIC secundário.
-pub const PIC_2_OFFSET: u8 = PIC_1_OFFSET + 8;
-
-/// O controlador de interrupções programável (PIC) global.
+/// O Local APIC global, instalado por `init_apic` no lugar do PIC 8259
+/// legado.
 ///
-/// É protegido por um `Mutex` para garantir o acesso seguro de múltiplos contextos.
-pub static PICS: spin::Mutex<ChainedPics> =
-    spin::Mutex::new(unsafe { ChainedPics::new(PIC_1_OFFSET, PIC_2_OFFSET) });
+/// É `None` até que `apic::init` tenha sido chamado; os handlers de timer e
+/// teclado assumem que já está populado quando disparam.
+pub static LOCAL_APIC: spin::Mutex<Option<apic::LocalApic>> = spin::Mutex::new(None);
 
 /// Enumeração dos índices de interrupção de hardware.
 #[derive(Debug, Clone, Copy)]
 #[repr(u8)]
 pub enum InterruptIndex {
-    /// Interrupção do timer (PIT).
-    Timer = PIC_1_OFFSET,
-    /// Interrupção do teclado.
-    Keyboard,
+    /// Interrupção do timer do Local APIC.
+    Timer = apic::TIMER_VECTOR,
+    /// Interrupção do teclado, roteada via I/O APIC.
+    Keyboard = apic::KEYBOARD_VECTOR,
+    /// Interrupção de software usada por um yield voluntário (não avança
+    /// `TICKS`, ao contrário de `Timer`).
+    Yield = apic::YIELD_VECTOR,
 }
 
 impl InterruptIndex {
@@ -51,12 +49,17 @@ lazy_static! {
         idt.breakpoint.set_handler_fn(breakpoint_handler);
         idt.page_fault.set_handler_fn(page_fault_handler);
         idt.divide_error.set_handler_fn(division_by_zero_handler);
+        idt.general_protection_fault.set_handler_fn(general_protection_fault_handler);
+        idt.stack_segment_fault.set_handler_fn(stack_segment_fault_handler);
+        idt.segment_not_present.set_handler_fn(segment_not_present_handler);
+        idt.invalid_opcode.set_handler_fn(invalid_opcode_handler);
         unsafe {
             idt.double_fault.set_handler_fn(double_fault_handler)
                 .set_stack_index(gdt::DOUBLE_FAULT_IST_INDEX);
         }
         idt[InterruptIndex::Timer.as_usize()].set_handler_fn(timer_interrupt_handler);
         idt[InterruptIndex::Keyboard.as_usize()].set_handler_fn(keyboard_interrupt_handler);
+        idt[InterruptIndex::Yield.as_usize()].set_handler_fn(yield_interrupt_handler);
         idt
     };
 }
@@ -68,39 +71,42 @@ pub fn init_idt() {
     IDT.load();
 }
 
-lazy_static! {
-    /// O driver de teclado global.
-    ///
-    /// É protegido por um `Mutex` para lidar com o acesso concorrente.
-    static ref KEYBOARD: spin::Mutex<Keyboard<layouts::Us104Key, ScancodeSet1>> =
-        spin::Mutex::new(Keyboard::new(layouts::Us104Key, ScancodeSet1,
-            HandleControl::Ignore)
-        );
+/// Inicializa o APIC/ACPI e desativa o PIC 8259 legado.
+///
+/// Deve ser chamada depois de `init_idt`, com a memória física já mapeada
+/// linearmente em `physical_memory_offset` pelo módulo `memory`.
+///
+/// # Safety
+///
+/// `rsdp_addr`, `mapper` e `frame_allocator` devem satisfazer os mesmos
+/// requisitos de `apic::init`.
+pub unsafe fn init_apic(
+    rsdp_addr: x86_64::VirtAddr,
+    mapper: &mut impl x86_64::structures::paging::Mapper<x86_64::structures::paging::Size4KiB>,
+    frame_allocator: &mut impl x86_64::structures::paging::FrameAllocator<
+        x86_64::structures::paging::Size4KiB,
+    >,
+) {
+    let (lapic, _ioapic) = apic::init(rsdp_addr, mapper, frame_allocator);
+    *LOCAL_APIC.lock() = Some(lapic);
 }
 
 /// Handler para a interrupção do teclado.
 ///
-/// Lê o scancode da porta do teclado, o decodifica e o imprime na tela.
-/// Envia um sinal de "End of Interrupt" (EOI) ao PIC.
+/// Lê o scancode cru da porta do teclado e o empilha em
+/// `shell::SCANCODE_QUEUE` para ser decodificado fora do contexto de
+/// interrupção (ver `shell::shell_task`), já que a decodificação aciona
+/// alocações de `String`/`Vec` que não devem acontecer em um ISR.
+/// Envia um sinal de "End of Interrupt" ao Local APIC.
 extern "x86-interrupt" fn keyboard_interrupt_handler(_stack_frame: InterruptStackFrame) {
     use x86_64::instructions::port::Port;
 
-    let mut keyboard = KEYBOARD.lock();
     let mut port = Port::new(0x60);
-
     let scancode: u8 = unsafe { port.read() };
-    if let Ok(Some(key_event)) = keyboard.add_byte(scancode) {
-        if let Some(key) = keyboard.process_keyevent(key_event) {
-            match key {
-                DecodedKey::Unicode(character) => print!("{}", character),
-                DecodedKey::RawKey(key) => print!("{:?}", key),
-            }
-        }
-    }
+    crate::shell::push_scancode(scancode);
 
-    unsafe {
-        PICS.lock()
-            .notify_end_of_interrupt(InterruptIndex::Keyboard.as_u8());
+    if let Some(lapic) = LOCAL_APIC.lock().as_ref() {
+        lapic.end_of_interrupt();
     }
 }
 
@@ -129,6 +135,78 @@ extern "x86-interrupt" fn division_by_zero_handler(stack_frame: InterruptStackFr
     loop {}
 }
 
+/// Decodifica um código de erro relacionado a seletor em seus campos:
+/// índice do descritor, indicador de tabela (GDT/LDT/IDT) e flag externa.
+fn print_selector_error_code(error_code: u64) {
+    let external = error_code & 0b1 != 0;
+    let idt = error_code & 0b10 != 0;
+    let table_index = (error_code >> 1) & 0b11;
+    let selector_index = (error_code >> 3) & 0x1FFF;
+
+    let table = if idt {
+        "IDT"
+    } else if table_index == 0b00 {
+        "GDT"
+    } else {
+        "LDT"
+    };
+
+    println!(
+        "Error Code: {:#x} (table={}, selector_index={}, external={})",
+        error_code, table, selector_index, external
+    );
+}
+
+/// Handler para a exceção de proteção geral.
+///
+/// Dispara quando a CPU encontra uma violação de proteção que não se encaixa
+/// em nenhuma outra exceção (ex.: acesso a um seletor inválido).
+extern "x86-interrupt" fn general_protection_fault_handler(
+    stack_frame: InterruptStackFrame,
+    error_code: u64,
+) {
+    println!("EXCEPTION: GENERAL PROTECTION FAULT");
+    println!("Faulting Instruction Pointer: {:?}", stack_frame.instruction_pointer);
+    print_selector_error_code(error_code);
+    println!("{:#?}", stack_frame);
+    loop {}
+}
+
+/// Handler para a exceção de falha de segmento de pilha.
+extern "x86-interrupt" fn stack_segment_fault_handler(
+    stack_frame: InterruptStackFrame,
+    error_code: u64,
+) {
+    println!("EXCEPTION: STACK SEGMENT FAULT");
+    println!("Faulting Instruction Pointer: {:?}", stack_frame.instruction_pointer);
+    print_selector_error_code(error_code);
+    println!("{:#?}", stack_frame);
+    loop {}
+}
+
+/// Handler para a exceção de segmento não presente.
+extern "x86-interrupt" fn segment_not_present_handler(
+    stack_frame: InterruptStackFrame,
+    error_code: u64,
+) {
+    println!("EXCEPTION: SEGMENT NOT PRESENT");
+    println!("Faulting Instruction Pointer: {:?}", stack_frame.instruction_pointer);
+    print_selector_error_code(error_code);
+    println!("{:#?}", stack_frame);
+    loop {}
+}
+
+/// Handler para a exceção de opcode inválido.
+///
+/// Não possui código de erro; a CPU aponta diretamente para a instrução
+/// inválida via `instruction_pointer`.
+extern "x86-interrupt" fn invalid_opcode_handler(stack_frame: InterruptStackFrame) {
+    println!("EXCEPTION: INVALID OPCODE");
+    println!("Faulting Instruction Pointer: {:?}", stack_frame.instruction_pointer);
+    println!("{:#?}", stack_frame);
+    loop {}
+}
+
 /// Handler para a exceção de double fault.
 extern "x86-interrupt" fn double_fault_handler(
     stack_frame: InterruptStackFrame,
@@ -139,18 +217,39 @@ extern "x86-interrupt" fn double_fault_handler(
 
 /// Handler para a interrupção do timer.
 ///
-/// Aciona o scheduler para realizar a troca de contexto.
+/// Avança o relógio global (acordando tarefas cujo `sleep`/`receive_timeout`
+/// tenha vencido) e aciona o scheduler para realizar a troca de contexto.
 /// Envia um sinal de "End of Interrupt" (EOI) ao PIC.
 extern "x86-interrupt" fn timer_interrupt_handler(_stack_frame: InterruptStackFrame) {
-    use crate::task::context_switch;
-    use crate::SCHEDULER;
+    use crate::task::{advance_clock, context_switch, current_scheduler};
 
-    unsafe {
-        PICS.lock()
-            .notify_end_of_interrupt(InterruptIndex::Timer.as_u8());
+    if let Some(lapic) = LOCAL_APIC.lock().as_ref() {
+        lapic.end_of_interrupt();
     }
 
-    let mut scheduler = SCHEDULER.lock();
+    advance_clock();
+
+    let mut scheduler = current_scheduler().lock();
+    if let Some((current_context, next_context)) = scheduler.schedule() {
+        let current_context_ptr = current_context as *mut _;
+        let next_context_ptr = next_context as *const _;
+        drop(scheduler);
+        unsafe {
+            context_switch(current_context_ptr, next_context_ptr);
+        }
+    }
+}
+
+/// Handler do yield voluntário (`InterruptIndex::Yield`).
+///
+/// Aciona o scheduler da mesma forma que `timer_interrupt_handler`, mas sem
+/// enviar EOI (não é uma IRQ de hardware, o Local APIC não está esperando
+/// um) e sem chamar `advance_clock`: um yield voluntário não deve fazer
+/// `TICKS`/o relógio do timer-wheel avançar, só o timer real.
+extern "x86-interrupt" fn yield_interrupt_handler(_stack_frame: InterruptStackFrame) {
+    use crate::task::{context_switch, current_scheduler};
+
+    let mut scheduler = current_scheduler().lock();
     if let Some((current_context, next_context)) = scheduler.schedule() {
         let current_context_ptr = current_context as *mut _;
         let next_context_ptr = next_context as *const _;
@@ -0,0 +1,119 @@
+//! Biblioteca do kernel, usada tanto pelo binário principal quanto pelos
+//! testes de integração.
+//!
+//! Os testes rodam dentro do próprio QEMU (não há outro jeito de exercitar
+//! código `no_std`/bare-metal), então este módulo expõe o runner customizado
+//! e o mecanismo de saída via a porta `isa-debug-exit`, que permite ao QEMU
+//! terminar com um código de saída distinguível em vez de ficar preso num
+//! `loop {}`.
+
+#![no_std]
+#![cfg_attr(test, no_main)]
+#![feature(custom_test_frameworks)]
+#![test_runner(crate::test_runner)]
+#![reexport_test_harness_main = "test_main"]
+
+use core::panic::PanicInfo;
+
+pub mod allocator;
+pub mod apic;
+pub mod executor;
+pub mod gdt;
+pub mod interrupts;
+pub mod memory;
+pub mod serial;
+pub mod shell;
+pub mod sync;
+pub mod task;
+pub mod vga_buffer;
+
+extern crate alloc;
+
+/// Código de saída reportado ao host através da porta `isa-debug-exit`.
+///
+/// QEMU termina com o status `(code << 1) | 1`, então os dois valores
+/// escolhidos aqui viram exit codes de processo distintos e não-zero,
+/// fáceis de checar em um script de CI.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum QemuExitCode {
+    /// Todos os testes passaram.
+    Success = 0x10,
+    /// Pelo menos um teste falhou.
+    Failed = 0x11,
+}
+
+/// Escreve o código de saída na porta `isa-debug-exit` (0xf4) e encerra o QEMU.
+///
+/// Requer que o QEMU tenha sido iniciado com
+/// `-device isa-debug-exit,iobase=0xf4,iosize=0x04`.
+pub fn exit_qemu(exit_code: QemuExitCode) -> ! {
+    use x86_64::instructions::port::Port;
+
+    unsafe {
+        let mut port = Port::new(0xf4);
+        port.write(exit_code as u32);
+    }
+
+    // QEMU já deveria ter saído; este loop só cobre o caso (ex.: execução
+    // fora do QEMU) em que a escrita na porta não teve efeito algum.
+    loop {}
+}
+
+/// Um teste executável pelo runner customizado.
+///
+/// Implementado para qualquer `Fn()`, de modo que funções de teste comuns
+/// (`#[test_case] fn foo() { ... }`) sejam aceitas diretamente.
+pub trait Testable {
+    /// Executa o teste, imprimindo seu nome antes e "[ok]"/"[failed]" depois.
+    fn run(&self);
+}
+
+impl<T> Testable for T
+where
+    T: Fn(),
+{
+    fn run(&self) {
+        serial_print!("{}...\t", core::any::type_name::<T>());
+        self();
+        serial_println!("[ok]");
+    }
+}
+
+/// Runner de testes customizado, registrado via `#![test_runner]`.
+///
+/// Executa cada teste, imprime o resultado na porta serial e, ao final,
+/// encerra o QEMU com `QemuExitCode::Success`. Um teste que entra em pânico
+/// é tratado pelo `panic_handler` de `#[cfg(test)]`, que encerra com
+/// `QemuExitCode::Failed` em vez de propagar o pânico.
+pub fn test_runner(tests: &[&dyn Testable]) {
+    serial_println!("Running {} tests", tests.len());
+    for test in tests {
+        test.run();
+    }
+    exit_qemu(QemuExitCode::Success);
+}
+
+/// Handler de pânico usado quando a biblioteca é compilada para os testes de
+/// integração (`cfg(test)`).
+///
+/// Em vez de travar em um `loop {}`, reporta a falha na porta serial e
+/// encerra o QEMU com `QemuExitCode::Failed`, para que o runner externo veja
+/// o teste como reprovado.
+#[cfg(test)]
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    serial_println!("[failed]\n");
+    serial_println!("Error: {}\n", info);
+    exit_qemu(QemuExitCode::Failed);
+}
+
+/// Ponto de entrada usado quando a lib é compilada como o harness de testes
+/// de integração (`cfg(test)`); apenas delega para o `test_main` gerado pelo
+/// framework de testes customizado.
+#[cfg(test)]
+#[no_mangle]
+pub extern "C" fn _start() -> ! {
+    test_main();
+    loop {}
+}
@@ -1,74 +1,91 @@
 #![no_std] // Sem biblioteca padrão - bare metal
 #![no_main] // Sem função main() padrão
 
+extern crate alloc;
+
+use bootloader::{entry_point, BootInfo};
 use core::panic::PanicInfo;
+use microkernel::task::Task;
+use microkernel::{allocator, apic, executor, gdt, interrupts, memory, println, shell, task, vga_buffer};
+use x86_64::registers::control::Cr3;
+use x86_64::structures::paging::PageTable;
+use x86_64::VirtAddr;
 
-// Constantes para cabeçalho Multiboot
-const MAGIC: u32 = 0x1BADB002; // Número mágico Multiboot
-const FLAGS: u32 = 0; // Flags de configuração
-const CHECKSUM: u32 = 0u32.wrapping_sub(MAGIC).wrapping_sub(FLAGS); // Checksum para validação
+entry_point!(kernel_main);
 
-// Estrutura do cabeçalho Multiboot
-#[repr(C)] // Layout compatível com C
-#[repr(align(4))] // Alinhamento de 4 bytes
-struct MultibootHeader {
-    magic: u32,
-    flags: u32,
-    checksum: u32,
-}
+/// Tamanho da pilha reservada para cada tarefa de kernel criada no boot.
+const TASK_STACK_SIZE: usize = 16 * 1024;
+
+/// Ponto de entrada do kernel, chamado pelo crate `bootloader` já em modo
+/// longo, com a própria paginação configurada e `boot_info` preenchido.
+///
+/// Inicializa, nesta ordem: GDT/TSS (a pilha do double fault depende dela já
+/// estar carregada), IDT, mapeamento física-para-virtual e heap, APIC/ACPI
+/// no lugar do PIC legado e, por fim, habilita interrupções e cria as
+/// tarefas do shell e do executor assíncrono.
+fn kernel_main(boot_info: &'static BootInfo) -> ! {
+    println!("*** MICROKERNEL RUST FUNCIONANDO! ***");
+    println!("Pressione Ctrl+Alt+G para sair do QEMU");
 
-// Cabeçalho Multiboot na seção especial
-#[used] // Força inclusão no binário final
-#[no_mangle] // Não alterar nome no linking
-#[link_section = ".multiboot_header"] // Seção específica no binário
-static MULTIBOOT_HEADER: MultibootHeader = MultibootHeader {
-    magic: MAGIC,
-    flags: FLAGS,
-    checksum: CHECKSUM,
-};
+    gdt::init();
+    interrupts::init_idt();
 
-// Ponto de entrada do kernel
-#[no_mangle] // Manter nome _start inalterado
-pub extern "C" fn _start() -> ! {
-    // Chamada externa C, nunca retorna
-    // Limpa a primeira linha da tela
-    let vga_buffer = 0xb8000 as *mut u8; // Endereço do buffer VGA
-    for i in 0..80 {
-        // 80 caracteres por linha
-        unsafe {
-            *vga_buffer.offset(i * 2) = b' '; // Caractere espaço
-            *vga_buffer.offset(i * 2 + 1) = 0x07; // Atributo: cinza claro sobre preto
-        }
+    let physical_memory_offset = VirtAddr::new(boot_info.physical_memory_offset);
+    let mut mapper = unsafe { memory::init(physical_memory_offset) };
+    unsafe {
+        memory::init_frame_allocator(&boot_info.memory_map);
     }
+    let mut frame_allocator = memory::GlobalFrameAllocator;
 
-    // Escreve mensagem principal
-    let hello = b"*** MICROKERNEL RUST FUNCIONANDO! ***";
-    let mut i = 0;
-    for &byte in hello.iter() {
-        unsafe {
-            *vga_buffer.offset(i as isize * 2) = byte; // Caractere
-            *vga_buffer.offset(i as isize * 2 + 1) = 0x0F; // Atributo: branco sobre preto
-        }
-        i += 1;
+    allocator::init_heap(&mut mapper, &mut frame_allocator).expect("falha ao inicializar o heap");
+
+    let rsdp_addr = unsafe { apic::find_rsdp() }.expect("RSDP não encontrado");
+    unsafe {
+        interrupts::init_apic(rsdp_addr, &mut mapper, &mut frame_allocator);
     }
 
-    // Adiciona segunda linha com instruções
-    let line2 = b"Pressione Ctrl+Alt+G para sair do QEMU";
-    let second_line_offset = 80 * 2; // Offset para segunda linha (80 chars * 2 bytes)
-    for (i, &byte) in line2.iter().enumerate() {
-        unsafe {
-            *vga_buffer.offset((second_line_offset + i * 2) as isize) = byte; // Caractere
-            *vga_buffer.offset((second_line_offset + i * 2 + 1) as isize) = 0x0E;
-            // Atributo: amarelo sobre preto
-        }
+    x86_64::instructions::interrupts::enable();
+
+    let kernel_p4_table = current_p4_table_ptr(physical_memory_offset);
+    spawn_kernel_task(shell::shell_task, kernel_p4_table);
+    spawn_kernel_task(executor::run_executor, kernel_p4_table);
+
+    loop {
+        x86_64::instructions::hlt();
     }
+}
+
+/// Lê o endereço físico da tabela P4 ativa (via `CR3`) e o traduz para
+/// virtual, sem criar uma segunda referência `&mut` para ela (ao contrário
+/// de `memory::active_level_4_table`, já usada internamente por
+/// `memory::init` para a mesma tabela).
+///
+/// Todas as tarefas de kernel criadas no boot compartilham este mesmo
+/// ponteiro: ainda não há isolamento de espaço de endereçamento por tarefa,
+/// então `Task::p4_table` só o guarda para uso futuro.
+fn current_p4_table_ptr(physical_memory_offset: VirtAddr) -> *mut PageTable {
+    let (level_4_frame, _) = Cr3::read();
+    let virt = physical_memory_offset + level_4_frame.start_address().as_u64();
+    virt.as_mut_ptr()
+}
 
-    loop {} // Loop infinito - mantém kernel rodando
+/// Aloca uma pilha dedicada para `entry` e registra uma `Task` para ela no
+/// `Scheduler`, via `task::spawn`.
+fn spawn_kernel_task(entry: fn() -> !, p4_table: *mut PageTable) {
+    let stack = alloc::vec![0u8; TASK_STACK_SIZE].leak();
+    let stack_top = VirtAddr::new(stack.as_ptr() as u64 + stack.len() as u64);
+    let entry_point = VirtAddr::new(entry as u64);
+    task::spawn(Task::new(entry_point, stack_top, p4_table));
 }
 
 // Tratador de pânico personalizado
 #[panic_handler]
-fn panic(_info: &PanicInfo) -> ! {
-    // Recebe info do pânico, nunca retorna
+fn panic(info: &PanicInfo) -> ! {
+    // Recebe info do pânico, nunca retorna.
+    // Pinta a tela inteira de vermelho com a mensagem de pânico e espelha o
+    // mesmo texto na porta serial, para que um kernel em pânico deixe um
+    // rastro legível tanto no monitor do QEMU quanto no terminal do host.
+    vga_buffer::panic_screen(format_args!("KERNEL PANIC: {}", info));
+    microkernel::serial_println!("KERNEL PANIC: {}", info);
     loop {} // Loop infinito em caso de pânico
 }
@@ -1,11 +1,19 @@
 //! Este módulo gerencia a memória, incluindo a paginação e a alocação de frames.
 
 use bootloader::bootinfo::{MemoryMap, MemoryRegionType};
+use core::sync::atomic::{AtomicU64, Ordering};
+use spin::Mutex;
 use x86_64::{
     structures::paging::{FrameAllocator, OffsetPageTable, PageTable, PhysFrame, Size4KiB},
     PhysAddr, VirtAddr,
 };
 
+/// O offset de memória física-para-virtual usado para traduzir endereços
+/// físicos para o mapeamento linear criado em `init`.
+///
+/// É preenchido uma única vez em `init` e lido por `phys_to_virt`.
+static PHYSICAL_MEMORY_OFFSET: AtomicU64 = AtomicU64::new(0);
+
 /// Inicializa uma nova `OffsetPageTable`.
 ///
 /// Esta função é insegura porque o chamador deve garantir que a memória física
@@ -13,10 +21,20 @@ use x86_64::{
 /// Além disso, esta função deve ser chamada apenas uma vez para evitar a criação de
 /// múltiplas referências `&mut` para a mesma memória, o que é um comportamento indefinido.
 pub unsafe fn init(physical_memory_offset: VirtAddr) -> OffsetPageTable<'static> {
+    PHYSICAL_MEMORY_OFFSET.store(physical_memory_offset.as_u64(), Ordering::Relaxed);
     let level_4_table = active_level_4_table(physical_memory_offset);
     OffsetPageTable::new(level_4_table, physical_memory_offset)
 }
 
+/// Traduz um endereço físico para o endereço virtual correspondente no
+/// mapeamento linear de memória física instalado por `init`.
+///
+/// Deve ser chamada apenas após `init`; antes disso o offset é 0 e o
+/// endereço retornado seria inválido.
+pub fn phys_to_virt(phys: PhysAddr) -> VirtAddr {
+    VirtAddr::new(PHYSICAL_MEMORY_OFFSET.load(Ordering::Relaxed) + phys.as_u64())
+}
+
 /// Retorna uma referência mutável para a tabela de nível 4 ativa.
 ///
 /// Esta função é insegura pelos mesmos motivos que a função `init`.
@@ -33,28 +51,65 @@ pub unsafe fn active_level_4_table(physical_memory_offset: VirtAddr) -> &'static
 }
 
 /// Um `FrameAllocator` que retorna frames usáveis a partir do mapa de memória do bootloader.
+///
+/// Os frames livres formam uma pilha intrusiva: cada frame livre guarda, nos
+/// seus primeiros 16 bytes (acessados via o mapeamento física-para-virtual),
+/// um `Option<PhysFrame>` com o próximo frame livre da pilha. Isso dá `allocate_frame`/
+/// `deallocate_frame` em O(1) sem precisar de nenhuma alocação extra no heap,
+/// ao custo de exigir que `phys_to_virt` já esteja utilizável.
 pub struct BootInfoFrameAllocator {
     memory_map: &'static MemoryMap,
-    next: usize,
+    /// Topo da pilha de frames livres, ou `None` se vazia.
+    free_list_head: Option<PhysFrame>,
+    /// Total de frames usáveis relatados por `memory_map`, fixado em `init`.
+    usable_frame_count: usize,
+    /// Quantos frames estão livres na pilha agora, mantido por
+    /// `push_free_frame`/`pop_free_frame`. Usado por `frame_stats`.
+    free_frame_count: usize,
 }
 
 impl BootInfoFrameAllocator {
     /// Cria um `FrameAllocator` a partir do mapa de memória passado.
     ///
     /// Esta função é insegura porque o chamador deve garantir que o mapa de memória
-    /// passado é válido. O principal requisito é que todos os frames marcados
-    /// como `USABLE` estejam realmente não utilizados.
+    /// passado é válido, que todos os frames marcados como `USABLE` estejam
+    /// realmente não utilizados, e que `memory::init` já tenha sido chamada
+    /// (a pilha intrusiva depende de `phys_to_virt`).
+    ///
+    /// Empilha todos os frames usáveis de uma vez, em ordem crescente de
+    /// endereço; como é uma pilha, a primeira alocação devolve o frame de
+    /// maior endereço.
+    ///
+    /// `usable_frames` é uma função associada sobre `memory_map` (não um
+    /// método `&self`) justamente para que seu iterador seja `'static`, sem
+    /// nenhuma relação de empréstimo com `allocator`: assim o `for` pode
+    /// chamar `allocator.push_free_frame(frame)` (que exige `&mut self`) a
+    /// cada iteração sem conflitar com um empréstimo imutável do próprio
+    /// `allocator` ainda vivo por causa do iterador.
     pub unsafe fn init(memory_map: &'static MemoryMap) -> Self {
-        BootInfoFrameAllocator {
+        let mut allocator = BootInfoFrameAllocator {
             memory_map,
-            next: 0,
+            free_list_head: None,
+            usable_frame_count: 0,
+            free_frame_count: 0,
+        };
+
+        for frame in Self::usable_frames(memory_map) {
+            allocator.usable_frame_count += 1;
+            allocator.push_free_frame(frame);
         }
+
+        allocator
     }
 
-    /// Retorna um iterador sobre os frames usáveis no mapa de memória.
-    fn usable_frames(&self) -> impl Iterator<Item = PhysFrame> {
+    /// Retorna um iterador sobre os frames usáveis em `memory_map`.
+    ///
+    /// É uma função associada, e não um método `&self`, para que o iterador
+    /// retornado não fique emprestado de nenhuma instância de
+    /// `BootInfoFrameAllocator` (ver `init`).
+    fn usable_frames(memory_map: &'static MemoryMap) -> impl Iterator<Item = PhysFrame> {
         // obtém as regiões usáveis do mapa de memória
-        let regions = self.memory_map.iter();
+        let regions = memory_map.iter();
         let usable_regions = regions
             .filter(|r| r.region_type == MemoryRegionType::Usable);
         // mapeia cada região para seu intervalo de endereços
@@ -65,15 +120,97 @@ impl BootInfoFrameAllocator {
         // cria tipos `PhysFrame` a partir dos endereços de início
         frame_addresses.map(|addr| PhysFrame::containing_address(PhysAddr::new(addr)))
     }
+
+    /// Empilha `frame` no topo da pilha de frames livres, escrevendo o
+    /// antigo topo (um `Option<PhysFrame>`) no início do frame.
+    unsafe fn push_free_frame(&mut self, frame: PhysFrame) {
+        let next_ptr = phys_to_virt(frame.start_address()).as_mut_ptr::<Option<PhysFrame>>();
+        next_ptr.write(self.free_list_head);
+        self.free_list_head = Some(frame);
+        self.free_frame_count += 1;
+    }
+
+    /// Desempilha e retorna o topo da pilha de frames livres, se houver.
+    unsafe fn pop_free_frame(&mut self) -> Option<PhysFrame> {
+        let frame = self.free_list_head?;
+        let next_ptr = phys_to_virt(frame.start_address()).as_ptr::<Option<PhysFrame>>();
+        self.free_list_head = next_ptr.read();
+        self.free_frame_count -= 1;
+        Some(frame)
+    }
+
+    /// Devolve `frame` ao alocador para reutilização futura.
+    ///
+    /// # Safety
+    ///
+    /// O chamador deve garantir que `frame` não está mais mapeado/em uso em
+    /// nenhuma tabela de páginas.
+    pub unsafe fn deallocate_frame(&mut self, frame: PhysFrame) {
+        self.push_free_frame(frame);
+    }
 }
 
 unsafe impl FrameAllocator<Size4KiB> for BootInfoFrameAllocator {
-    /// Aloca um frame de 4KiB.
+    /// Aloca um frame de 4KiB em O(1), desempilhando o topo da pilha de
+    /// frames livres.
     ///
     /// Retorna `None` se não houver mais frames disponíveis.
     fn allocate_frame(&mut self) -> Option<PhysFrame> {
-        let frame = self.usable_frames().nth(self.next);
-        self.next += 1;
-        frame
+        unsafe { self.pop_free_frame() }
+    }
+}
+
+/// O `BootInfoFrameAllocator` compartilhado entre os consumidores do boot
+/// sequence (`allocator::init_heap`, `apic::init`) e o comando `mem` do
+/// shell, instalado por `init_frame_allocator`.
+///
+/// `None` até que `init_frame_allocator` seja chamada.
+static FRAME_ALLOCATOR: Mutex<Option<BootInfoFrameAllocator>> = Mutex::new(None);
+
+/// Instala o `FRAME_ALLOCATOR` global a partir de `memory_map`.
+///
+/// # Safety
+///
+/// Mesmos requisitos de `BootInfoFrameAllocator::init`; deve ser chamada uma
+/// única vez, depois de `memory::init`.
+pub unsafe fn init_frame_allocator(memory_map: &'static MemoryMap) {
+    *FRAME_ALLOCATOR.lock() = Some(BootInfoFrameAllocator::init(memory_map));
+}
+
+/// `FrameAllocator` de tamanho zero que delega para o `FRAME_ALLOCATOR`
+/// global.
+///
+/// Existe para que vários chamadores independentes (inicialização do heap,
+/// mapeamento de MMIO do APIC) compartilhem a mesma instância de
+/// `BootInfoFrameAllocator`, sem precisar passar uma referência `&mut` única
+/// através de todo o boot sequence.
+pub struct GlobalFrameAllocator;
+
+unsafe impl FrameAllocator<Size4KiB> for GlobalFrameAllocator {
+    fn allocate_frame(&mut self) -> Option<PhysFrame> {
+        FRAME_ALLOCATOR
+            .lock()
+            .as_mut()
+            .expect("GlobalFrameAllocator usado antes de init_frame_allocator")
+            .allocate_frame()
     }
 }
+
+/// Estatísticas do `FRAME_ALLOCATOR` global, usadas pelo comando `mem` do shell.
+#[derive(Debug, Clone, Copy)]
+pub struct FrameStats {
+    /// Total de frames usáveis relatados pelo mapa de memória do bootloader.
+    pub usable_frames: usize,
+    /// Frames ainda livres na pilha intrusiva.
+    pub free_frames: usize,
+}
+
+/// Lê as estatísticas atuais do `FRAME_ALLOCATOR` global.
+///
+/// Retorna `None` se `init_frame_allocator` ainda não tiver sido chamada.
+pub fn frame_stats() -> Option<FrameStats> {
+    FRAME_ALLOCATOR.lock().as_ref().map(|allocator| FrameStats {
+        usable_frames: allocator.usable_frame_count,
+        free_frames: allocator.free_frame_count,
+    })
+}
@@ -0,0 +1,118 @@
+//! Este módulo implementa um driver para a UART 16550, usado para enviar
+//! logs do kernel para a porta serial do QEMU (`-serial stdio`).
+
+use core::fmt;
+use lazy_static::lazy_static;
+use spin::Mutex;
+use x86_64::instructions::port::Port;
+
+/// Porta base da primeira UART (COM1).
+const COM1_BASE: u16 = 0x3F8;
+
+/// Driver mínimo para uma UART 16550 em modo polling.
+pub struct SerialPort {
+    /// Porta base informada a `new`; os registradores extras usados só em
+    /// `init` (divisor de baud rate, FIFO, modem control) são recalculados
+    /// a partir dela em vez de assumir `COM1_BASE`, para que uma porta
+    /// diferente de COM1 também seja configurada corretamente.
+    base: u16,
+    data: Port<u8>,
+    interrupt_enable: Port<u8>,
+    line_control: Port<u8>,
+    line_status: Port<u8>,
+}
+
+impl SerialPort {
+    /// Cria um driver para a UART na porta base informada.
+    ///
+    /// A porta ainda não está configurada; chame `init` antes do primeiro uso.
+    const fn new(base: u16) -> Self {
+        SerialPort {
+            base,
+            data: Port::new(base),
+            interrupt_enable: Port::new(base + 1),
+            line_control: Port::new(base + 3),
+            line_status: Port::new(base + 5),
+        }
+    }
+
+    /// Configura a UART: desabilita interrupções, define o divisor de baud
+    /// rate (DLAB), configura 8 bits de dados / sem paridade / 1 stop bit
+    /// (8N1), e habilita o FIFO.
+    fn init(&mut self) {
+        let base = self.base;
+        unsafe {
+            // Desabilita interrupções da UART; usamos polling.
+            self.interrupt_enable.write(0x00);
+
+            // Habilita DLAB para programar o divisor de baud rate.
+            self.line_control.write(0x80);
+            let mut divisor_low = Port::<u8>::new(base);
+            let mut divisor_high = Port::<u8>::new(base + 1);
+            // Divisor 3 = 38400 baud, com o clock padrão de 115200 Hz.
+            divisor_low.write(0x03);
+            divisor_high.write(0x00);
+
+            // 8N1 e desliga o DLAB.
+            self.line_control.write(0x03);
+
+            // Habilita e limpa o FIFO, com threshold de 14 bytes.
+            let mut fifo_control = Port::<u8>::new(base + 2);
+            fifo_control.write(0xC7);
+
+            // Habilita DTR, RTS e OUT2 (necessário em hardware real para IRQs).
+            let mut modem_control = Port::<u8>::new(base + 4);
+            modem_control.write(0x0B);
+        }
+    }
+
+    /// Aguarda até que o transmissor esteja vazio e então envia um byte.
+    fn send(&mut self, byte: u8) {
+        unsafe {
+            while self.line_status.read() & 0x20 == 0 {}
+            self.data.write(byte);
+        }
+    }
+}
+
+impl fmt::Write for SerialPort {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for byte in s.bytes() {
+            self.send(byte);
+        }
+        Ok(())
+    }
+}
+
+lazy_static! {
+    /// A porta serial global (COM1), protegida por um `Mutex`.
+    pub static ref SERIAL1: Mutex<SerialPort> = {
+        let mut serial_port = SerialPort::new(COM1_BASE);
+        serial_port.init();
+        Mutex::new(serial_port)
+    };
+}
+
+/// Macro para imprimir uma string formatada na porta serial.
+#[macro_export]
+macro_rules! serial_print {
+    ($($arg:tt)*) => ($crate::serial::_print(format_args!($($arg)*)));
+}
+
+/// Macro para imprimir uma string formatada na porta serial, com uma nova
+/// linha no final.
+#[macro_export]
+macro_rules! serial_println {
+    () => ($crate::serial_print!("\n"));
+    ($($arg:tt)*) => ($crate::serial_print!("{}\n", format_args!($($arg)*)));
+}
+
+/// Função auxiliar privada usada pelas macros `serial_print!` e `serial_println!`.
+#[doc(hidden)]
+pub fn _print(args: fmt::Arguments) {
+    use core::fmt::Write;
+    SERIAL1
+        .lock()
+        .write_fmt(args)
+        .expect("impressão na porta serial falhou");
+}
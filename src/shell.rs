@@ -0,0 +1,186 @@
+//! Este módulo implementa um shell de linha simples sobre o teclado,
+//! com eco, backspace, histórico de comandos e alguns comandos embutidos.
+//!
+//! O trabalho de decodificação e montagem da linha roda fora do contexto de
+//! interrupção: o handler de teclado apenas empilha o scancode em
+//! `SCANCODE_QUEUE`, e esta tarefa consome a fila, fazendo as alocações de
+//! `String`/`Vec` em um contexto onde isso é seguro.
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use crossbeam_queue::ArrayQueue;
+use lazy_static::lazy_static;
+use pc_keyboard::{layouts, DecodedKey, HandleControl, Keyboard, KeyCode, KeyEvent, ScancodeSet1};
+use spin::Mutex;
+
+use crate::{clear_screen, print, println};
+
+/// Capacidade da fila de scancodes pendentes.
+const QUEUE_CAPACITY: usize = 128;
+
+lazy_static! {
+    /// Fila de scancodes crus, preenchida pelo handler de interrupção do
+    /// teclado e consumida por `shell_task`.
+    static ref SCANCODE_QUEUE: ArrayQueue<u8> = ArrayQueue::new(QUEUE_CAPACITY);
+}
+
+/// Empilha um scancode recebido na interrupção do teclado.
+///
+/// Não aloca: se a fila estiver cheia, o scancode é descartado silenciosamente.
+/// Deve ser chamada apenas a partir do handler de interrupção.
+pub fn push_scancode(scancode: u8) {
+    let _ = SCANCODE_QUEUE.push(scancode);
+}
+
+/// Histórico de linhas de comando já executadas, do mais antigo ao mais
+/// recente.
+static PREV_COMMANDS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// Estado acumulado da linha de comando sendo digitada.
+struct Shell {
+    input: String,
+    /// Índice no histórico sendo visualizado via Up/Down, se houver.
+    history_cursor: Option<usize>,
+}
+
+impl Shell {
+    const fn new() -> Self {
+        Shell {
+            input: String::new(),
+            history_cursor: None,
+        }
+    }
+
+    /// Adiciona um caractere à linha atual e o ecoa na tela.
+    fn push_char(&mut self, c: char) {
+        self.input.push(c);
+        print!("{}", c);
+    }
+
+    /// Apaga o último caractere da linha atual, se houver, sobrescrevendo a
+    /// célula correspondente do VGA.
+    fn backspace(&mut self) {
+        if self.input.pop().is_some() {
+            print!("\u{8} \u{8}");
+        }
+    }
+
+    /// Conclui a linha atual: ecoa a quebra de linha, despacha o comando e
+    /// limpa o buffer para a próxima linha.
+    fn enter(&mut self) {
+        println!();
+        let line = core::mem::take(&mut self.input);
+        if !line.is_empty() {
+            dispatch_command(&line);
+            PREV_COMMANDS.lock().push(line);
+        }
+        self.history_cursor = None;
+    }
+
+    /// Substitui a linha atual pela entrada de histórico anterior/seguinte,
+    /// reescrevendo visualmente a entrada.
+    fn cycle_history(&mut self, older: bool) {
+        let history = PREV_COMMANDS.lock();
+        if history.is_empty() {
+            return;
+        }
+
+        let next_cursor = match (self.history_cursor, older) {
+            (None, true) => Some(history.len() - 1),
+            (Some(i), true) => Some(i.saturating_sub(1)),
+            (Some(i), false) if i + 1 < history.len() => Some(i + 1),
+            (Some(_), false) => None,
+            (None, false) => None,
+        };
+
+        self.rewrite_input(next_cursor.map(|i| history[i].clone()).unwrap_or_default());
+        self.history_cursor = next_cursor;
+    }
+
+    /// Apaga visualmente a linha atual e a substitui por `new_input`.
+    fn rewrite_input(&mut self, new_input: String) {
+        for _ in 0..self.input.len() {
+            print!("\u{8} \u{8}");
+        }
+        print!("{}", new_input);
+        self.input = new_input;
+    }
+}
+
+/// Executa um comando embutido conhecido, ou reporta erro se desconhecido.
+fn dispatch_command(line: &str) {
+    match line.trim() {
+        "clear" => clear_screen!(),
+        "mem" => print_mem_stats(),
+        "" => {}
+        other => println!("comando desconhecido: {}", other),
+    }
+}
+
+/// Imprime estatísticas do alocador de frames, para o comando `mem`.
+fn print_mem_stats() {
+    match crate::memory::frame_stats() {
+        Some(stats) => {
+            let used_frames = stats.usable_frames - stats.free_frames;
+            println!(
+                "mem: {} frames usaveis, {} livres, {} em uso ({} KiB livres)",
+                stats.usable_frames,
+                stats.free_frames,
+                used_frames,
+                stats.free_frames * 4
+            );
+        }
+        None => println!("mem: alocador de frames ainda nao inicializado"),
+    }
+}
+
+/// Tarefa de shell: consome `SCANCODE_QUEUE`, decodifica teclas e mantém o
+/// estado da linha de comando.
+///
+/// Roda como uma `Task` normal do scheduler; quando a fila está vazia, cede
+/// o tempo de CPU com `yield_now` em vez de ocupar a CPU em busy-wait.
+pub fn shell_task() -> ! {
+    let mut shell = Shell::new();
+    let mut keyboard = Keyboard::new(layouts::Us104Key, ScancodeSet1, HandleControl::Ignore);
+
+    loop {
+        match SCANCODE_QUEUE.pop() {
+            Some(scancode) => {
+                if let Ok(Some(key_event)) = keyboard.add_byte(scancode) {
+                    handle_key_event(&mut shell, &mut keyboard, key_event);
+                }
+            }
+            None => crate::task::yield_now(),
+        }
+    }
+}
+
+/// Traduz um `KeyEvent` já decodificado pelo driver em uma ação do shell,
+/// tratando Backspace, Enter e as setas de histórico separadamente do
+/// caminho normal de eco de caracteres.
+fn handle_key_event(
+    shell: &mut Shell,
+    keyboard: &mut Keyboard<layouts::Us104Key, ScancodeSet1>,
+    key_event: KeyEvent,
+) {
+    match (key_event.code, key_event.state) {
+        (KeyCode::ArrowUp, pc_keyboard::KeyState::Down) => {
+            shell.cycle_history(true);
+            return;
+        }
+        (KeyCode::ArrowDown, pc_keyboard::KeyState::Down) => {
+            shell.cycle_history(false);
+            return;
+        }
+        _ => {}
+    }
+
+    if let Some(key) = keyboard.process_keyevent(key_event) {
+        match key {
+            DecodedKey::Unicode('\u{8}') => shell.backspace(),
+            DecodedKey::Unicode('\n') => shell.enter(),
+            DecodedKey::Unicode(c) => shell.push_char(c),
+            DecodedKey::RawKey(_) => {}
+        }
+    }
+}
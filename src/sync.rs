@@ -34,12 +34,12 @@ impl Semaphore {
                 continue;
             }
 
-            let my_id = crate::SCHEDULER.lock().current_task_id();
+            let my_id = task::current_scheduler().lock().current_task_id();
             self.waiting_tasks.lock().push_back(my_id);
 
             // Block the task
             {
-                let mut scheduler = crate::SCHEDULER.lock();
+                let mut scheduler = task::current_scheduler().lock();
                 let current_task = scheduler.tasks.iter_mut().find(|t| t.id == my_id).unwrap();
                 current_task.state = TaskState::Blocked;
             }
@@ -55,10 +55,7 @@ impl Semaphore {
     pub fn up(&self) {
         self.counter.fetch_add(1, Ordering::Release);
         if let Some(task_id) = self.waiting_tasks.lock().pop_front() {
-            let mut scheduler = crate::SCHEDULER.lock();
-            if let Some(task) = scheduler.tasks.iter_mut().find(|t| t.id == task_id) {
-                task.state = TaskState::Ready;
-            }
+            task::with_task(task_id, |task| task.state = TaskState::Ready);
         }
     }
 }
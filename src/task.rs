@@ -8,6 +8,8 @@ use alloc::collections::VecDeque;
 use spin::Mutex;
 use lazy_static::lazy_static;
 use alloc::collections::BTreeMap;
+use core::task::Waker;
+use crossbeam_queue::SegQueue;
 
 // Inclui o código assembly para a troca de contexto.
 global_asm!(include_str!("task/context.s"));
@@ -37,6 +39,25 @@ impl TaskId {
     }
 }
 
+/// Identifica uma CPU lógica pelo seu Local APIC ID.
+///
+/// Hoje só a BSP (CPU de boot) roda de fato, então `current_cpu_id` sempre
+/// observa o mesmo valor; o tipo já é o que o bring-up de APs vai precisar
+/// para registrar as demais CPUs em `SCHEDULERS`.
+pub type CpuId = u32;
+
+/// Lê o `CpuId` da CPU que executa a chamada atual.
+///
+/// Antes de `apic::init` rodar (ou em testes que não inicializam o Local
+/// APIC), não há como ler o ID de hardware; assume-se `0`, o `CpuId` da BSP.
+pub fn current_cpu_id() -> CpuId {
+    crate::interrupts::LOCAL_APIC
+        .lock()
+        .as_ref()
+        .map(|lapic| lapic.id())
+        .unwrap_or(0)
+}
+
 /// O estado de uma tarefa.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TaskState {
@@ -48,15 +69,198 @@ pub enum TaskState {
     Blocked,
 }
 
-/// Representa uma mensagem que pode ser enviada entre tarefas.
-pub type Message = u64;
+/// Representa uma mensagem enviada entre tarefas: uma tag definida pelo
+/// chamador mais um payload de tamanho variável.
+#[derive(Debug, Clone)]
+pub struct Message {
+    /// Tag definida pelo chamador, usada para identificar o tipo/formato do payload.
+    pub tag: u32,
+    /// Payload da mensagem, de tamanho arbitrário.
+    pub payload: alloc::vec::Vec<u8>,
+}
+
+impl Message {
+    /// Cria uma nova mensagem com a tag e o payload informados.
+    pub fn new(tag: u32, payload: alloc::vec::Vec<u8>) -> Self {
+        Message { tag, payload }
+    }
+}
+
+/// Resultado de uma tentativa de envio.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SendResult {
+    /// A mensagem foi enfileirada com sucesso.
+    Sent,
+    /// A mailbox está cheia; a mensagem não foi enviada.
+    WouldBlock,
+    /// Não existe mailbox para o `TaskId` informado.
+    NoSuchTask,
+}
+
+/// Capacidade padrão de uma mailbox, em número de mensagens.
+const DEFAULT_MAILBOX_CAPACITY: usize = 32;
+
+/// Uma caixa de correio com capacidade limitada.
+///
+/// Quando cheia, remetentes bloqueantes (`send`) entram em `blocked_senders`
+/// e são acordados, um de cada vez, à medida que o receptor drena mensagens.
+struct Mailbox {
+    queue: VecDeque<Message>,
+    capacity: usize,
+    blocked_senders: VecDeque<TaskId>,
+}
+
+impl Mailbox {
+    fn new(capacity: usize) -> Self {
+        Mailbox {
+            queue: VecDeque::new(),
+            capacity,
+            blocked_senders: VecDeque::new(),
+        }
+    }
+}
 
 lazy_static! {
     /// O gerenciador de caixas de correio (mailboxes) global.
     ///
-    /// Mapeia cada `TaskId` a uma fila de mensagens (`VecDeque`).
-    static ref MAILBOXES: Mutex<BTreeMap<TaskId, VecDeque<Message>>> =
+    /// Mapeia cada `TaskId` à sua `Mailbox`.
+    static ref MAILBOXES: Mutex<BTreeMap<TaskId, Mailbox>> =
+        Mutex::new(BTreeMap::new());
+
+    /// Wakers registrados por tarefas assíncronas (via `executor::recv`)
+    /// aguardando a próxima mensagem de sua mailbox.
+    static ref MAILBOX_WAKERS: Mutex<BTreeMap<TaskId, Waker>> = Mutex::new(BTreeMap::new());
+
+    /// Registro do `Scheduler` de cada CPU, indexado pelo seu `CpuId`.
+    ///
+    /// `current_scheduler` só trava este registro para buscar ou criar a
+    /// entrada da CPU atual; a partir daí o chamador fica com a referência
+    /// `'static` ao `Mutex<Scheduler>` dessa CPU e não precisa mais do
+    /// registro para tomar decisões de `schedule()`.
+    static ref SCHEDULERS: Mutex<BTreeMap<CpuId, &'static Mutex<Scheduler>>> =
         Mutex::new(BTreeMap::new());
+
+    /// Roda do timer: tarefas com um `sleep`/`receive_timeout` pendente,
+    /// indexadas pelo tick absoluto em que devem acordar.
+    ///
+    /// Uma entrada pode ficar obsoleta (a tarefa já acordou por outro
+    /// motivo, ex. uma mensagem chegou primeiro); `advance_clock` confirma
+    /// contra `Task::wake_deadline` antes de agir, em vez de precisar
+    /// remover a entrada no momento em que ela deixa de valer.
+    static ref TIMER_WHEEL: Mutex<BTreeMap<u64, alloc::vec::Vec<TaskId>>> = Mutex::new(BTreeMap::new());
+}
+
+/// Contador global de ticks, avançado a cada interrupção de timer por
+/// `advance_clock`.
+static TICKS: AtomicU64 = AtomicU64::new(0);
+
+/// Retorna o tick absoluto atual do sistema.
+pub fn current_tick() -> u64 {
+    TICKS.load(Ordering::Relaxed)
+}
+
+/// Agenda o despertar de `task_id` no tick absoluto `wake_tick`, tanto na
+/// `TIMER_WHEEL` quanto em `Task::wake_deadline`.
+fn register_wake(task_id: TaskId, wake_tick: u64) {
+    TIMER_WHEEL.lock().entry(wake_tick).or_default().push(task_id);
+    with_task(task_id, |task| task.wake_deadline = Some(wake_tick));
+}
+
+/// Avança o relógio global em um tick e acorda toda tarefa cujo
+/// `wake_deadline` já tenha vencido.
+///
+/// Chamado pelo handler da interrupção de timer, antes de `schedule()`.
+pub fn advance_clock() {
+    let tick = TICKS.fetch_add(1, Ordering::Relaxed) + 1;
+
+    let due: alloc::vec::Vec<TaskId> = {
+        let mut wheel = TIMER_WHEEL.lock();
+        let due_ticks: alloc::vec::Vec<u64> = wheel.range(..=tick).map(|(&wake_tick, _)| wake_tick).collect();
+        due_ticks
+            .into_iter()
+            .filter_map(|wake_tick| wheel.remove(&wake_tick))
+            .flatten()
+            .collect()
+    };
+
+    for task_id in due {
+        with_task(task_id, |task| {
+            // Uma entrada obsoleta (a tarefa já foi acordada por uma
+            // mensagem, que zera `wake_deadline`, ou registrou desde então
+            // um prazo mais distante) não deve mexer no estado.
+            if task.wake_deadline.map_or(false, |deadline| deadline <= tick) {
+                task.wake_deadline = None;
+                if task.state == TaskState::Blocked {
+                    task.state = TaskState::Ready;
+                }
+            }
+        });
+    }
+}
+
+/// Fila de overflow compartilhada entre todas as CPUs.
+///
+/// Tarefas criadas via `spawn` (em vez de `Scheduler::add_task`, que as
+/// coloca direto na fila local de uma CPU já identificada) entram aqui; uma
+/// CPU sem nenhuma tarefa `Ready` na fila local a drena antes de tentar um
+/// `try_steal`.
+///
+/// É uma fila MPMC sem lock (`crossbeam_queue::SegQueue`) de propósito: ao
+/// contrário da fila local de cada `Scheduler`, esta é tocada por todas as
+/// CPUs, e um `spin::Mutex` aqui viraria um ponto de contenção a cada
+/// `schedule()`.
+static INJECTOR: SegQueue<Task> = SegQueue::new();
+
+/// Quantas tarefas, no máximo, `Scheduler::refill` tira do `INJECTOR` de uma
+/// vez, deixando o restante para outras CPUs ociosas.
+const INJECTOR_REFILL_BATCH: usize = 4;
+
+/// Retorna o `Mutex<Scheduler>` da CPU atual, criando-o com `PriorityPolicy`
+/// como política padrão na primeira chamada.
+pub fn current_scheduler() -> &'static Mutex<Scheduler> {
+    let cpu_id = current_cpu_id();
+
+    if let Some(scheduler) = SCHEDULERS.lock().get(&cpu_id) {
+        return scheduler;
+    }
+
+    let scheduler: &'static Mutex<Scheduler> = alloc::boxed::Box::leak(alloc::boxed::Box::new(
+        Mutex::new(Scheduler::with_policy(
+            cpu_id,
+            alloc::boxed::Box::new(PriorityPolicy::new()),
+        )),
+    ));
+    SCHEDULERS.lock().insert(cpu_id, scheduler);
+    scheduler
+}
+
+/// Cria a mailbox de `task` e a coloca no `INJECTOR`.
+///
+/// Use isto em vez de `Scheduler::add_task` para criar uma tarefa sem
+/// favorecer a CPU atual: ela só entra na fila local de alguém quando uma
+/// CPU ociosa a puxa em seu próximo `refill`.
+pub fn spawn(task: Task) {
+    MAILBOXES.lock().insert(task.id, Mailbox::new(DEFAULT_MAILBOX_CAPACITY));
+    INJECTOR.push(task);
+}
+
+/// Localiza a tarefa com o `TaskId` informado na fila local de alguma CPU
+/// registrada e aplica `f` a ela.
+///
+/// As rotinas de wake (`wake_receiver`, `wake_one_blocked_sender`, o `up` de
+/// `Semaphore`) precisam disto: a tarefa que se quer acordar pode estar
+/// bloqueada na fila de qualquer CPU, não só da atual.
+pub(crate) fn with_task<R>(task_id: TaskId, f: impl FnOnce(&mut Task) -> R) -> Option<R> {
+    let schedulers: alloc::vec::Vec<&'static Mutex<Scheduler>> =
+        SCHEDULERS.lock().values().copied().collect();
+
+    for scheduler in schedulers {
+        let mut scheduler = scheduler.lock();
+        if let Some(task) = scheduler.tasks.iter_mut().find(|t| t.id == task_id) {
+            return Some(f(task));
+        }
+    }
+    None
 }
 
 /// O contexto de uma tarefa, contendo o estado dos registradores da CPU.
@@ -93,6 +297,12 @@ struct UnsafeSendSync<T>(T);
 unsafe impl<T> Send for UnsafeSendSync<T> {}
 unsafe impl<T> Sync for UnsafeSendSync<T> {}
 
+/// A prioridade padrão atribuída a uma tarefa criada via `Task::new`.
+///
+/// Prioridades mais altas são servidas primeiro; tarefas de driver/console
+/// latência-sensíveis devem usar `set_priority` com um valor acima deste.
+pub const DEFAULT_PRIORITY: u8 = 128;
+
 /// Representa uma tarefa no sistema.
 pub struct Task {
     /// O ID único da tarefa.
@@ -101,12 +311,23 @@ pub struct Task {
     pub state: TaskState,
     /// O contexto da CPU da tarefa.
     pub context: TaskContext,
+    /// A prioridade base da tarefa, usada como ponto de partida da seleção
+    /// por `PriorityPolicy`.
+    pub priority: u8,
+    /// Tick absoluto em que `sleep`/`receive_timeout` devem acordar esta
+    /// tarefa, se houver um deles pendente.
+    ///
+    /// `advance_clock` zera este campo ao disparar o timeout; qualquer outra
+    /// rotina de wake (ex. `wake_receiver`, quando uma mensagem chega
+    /// primeiro) também o zera, para que `receive_timeout` saiba distinguir
+    /// as duas razões de ter acordado.
+    wake_deadline: Option<u64>,
     /// Ponteiro para a tabela de páginas P4 da tarefa.
     p4_table: UnsafeSendSync<*mut PageTable>,
 }
 
 impl Task {
-    /// Cria uma nova `Task`.
+    /// Cria uma nova `Task` com a prioridade padrão (`DEFAULT_PRIORITY`).
     pub fn new(entry_point: VirtAddr, stack_top: VirtAddr, p4_table: *mut PageTable) -> Self {
         Task {
             id: TaskId::new(),
@@ -121,49 +342,264 @@ impl Task {
                 r15: 0,
                 rip: entry_point,
             },
+            priority: DEFAULT_PRIORITY,
+            wake_deadline: None,
             p4_table: UnsafeSendSync(p4_table),
         }
     }
+
+    /// Define a prioridade base da tarefa.
+    pub fn set_priority(&mut self, priority: u8) {
+        self.priority = priority;
+    }
+}
+
+/// Decide qual tarefa `Ready` deve rodar em seguida.
+///
+/// Implementações recebem apenas uma visão imutável das tarefas: qualquer
+/// estado próprio da política (por exemplo, contadores de aging) deve ser
+/// mantido dentro da própria implementação, indexado por `TaskId`, e não
+/// escrito de volta em `Task`.
+pub trait SchedulingPolicy {
+    /// Escolhe o índice, em `tasks`, da próxima tarefa `Ready` a rodar, dado
+    /// o índice da tarefa atualmente em execução. Retorna `None` se nenhuma
+    /// outra tarefa estiver pronta.
+    fn pick_next(&mut self, tasks: &[Task], current: usize) -> Option<usize>;
+}
+
+/// Política round-robin simples: percorre as tarefas a partir da atual e
+/// escolhe a primeira que estiver `Ready`, ignorando prioridade.
+pub struct RoundRobin;
+
+impl SchedulingPolicy for RoundRobin {
+    fn pick_next(&mut self, tasks: &[Task], current: usize) -> Option<usize> {
+        let task_count = tasks.len();
+        for offset in 1..task_count {
+            let idx = (current + offset) % task_count;
+            if tasks[idx].state == TaskState::Ready {
+                return Some(idx);
+            }
+        }
+        None
+    }
+}
+
+/// Política que escolhe a tarefa `Ready` de maior prioridade efetiva,
+/// desempatando em ordem round-robin a partir da tarefa atual.
+///
+/// Para evitar starvation, tarefas `Ready` que não forem escolhidas em uma
+/// passada envelhecem (sua prioridade efetiva sobe); a tarefa escolhida tem a
+/// sua resetada para a prioridade base.
+#[derive(Default)]
+pub struct PriorityPolicy {
+    /// Prioridade efetiva corrente de cada tarefa, por `TaskId`. Ausente até
+    /// a primeira passada em que a tarefa é vista.
+    effective_priorities: BTreeMap<TaskId, u8>,
+}
+
+impl PriorityPolicy {
+    /// Cria uma `PriorityPolicy` sem nenhum estado de aging acumulado.
+    pub fn new() -> Self {
+        PriorityPolicy {
+            effective_priorities: BTreeMap::new(),
+        }
+    }
+
+    /// Lê (inicializando se ausente) a prioridade efetiva atual da tarefa.
+    fn effective_priority(&mut self, task: &Task) -> u8 {
+        *self
+            .effective_priorities
+            .entry(task.id)
+            .or_insert(task.priority)
+    }
+}
+
+impl SchedulingPolicy for PriorityPolicy {
+    fn pick_next(&mut self, tasks: &[Task], current: usize) -> Option<usize> {
+        let task_count = tasks.len();
+
+        let mut next_index = None;
+        let mut best_priority: i16 = -1;
+        for offset in 1..task_count {
+            let idx = (current + offset) % task_count;
+            if tasks[idx].state != TaskState::Ready {
+                continue;
+            }
+            let effective = self.effective_priority(&tasks[idx]) as i16;
+            if effective > best_priority {
+                best_priority = effective;
+                next_index = Some(idx);
+            }
+        }
+
+        let next_index = next_index?;
+
+        for offset in 1..task_count {
+            let idx = (current + offset) % task_count;
+            if idx != next_index && tasks[idx].state == TaskState::Ready {
+                let entry = self.effective_priorities.entry(tasks[idx].id).or_insert(tasks[idx].priority);
+                *entry = entry.saturating_add(1);
+            }
+        }
+        self.effective_priorities.insert(tasks[next_index].id, tasks[next_index].priority);
+
+        Some(next_index)
+    }
 }
 
 /// O scheduler de tarefas.
 ///
-/// Implementa uma política de escalonamento round-robin simples.
+/// A decisão de qual tarefa rodar em seguida é delegada a uma
+/// `SchedulingPolicy` plugável; o scheduler em si só cuida de manter a lista
+/// de tarefas e realizar a troca de contexto.
+///
+/// Cada CPU tem seu próprio `Scheduler`, obtido via `current_scheduler`, e
+/// só disputa o `Mutex` dele mesmo no caminho comum de `schedule()` — o de
+/// outra CPU só é travado por `try_steal`, e apenas pela CPU ociosa que está
+/// roubando.
 pub struct Scheduler {
     pub tasks: alloc::vec::Vec<Task>,
     current_task: usize,
+    policy: alloc::boxed::Box<dyn SchedulingPolicy + Send>,
+    cpu_id: CpuId,
 }
 
 impl Scheduler {
-    /// Cria um novo `Scheduler`.
-    pub fn new() -> Self {
+    /// Cria um novo `Scheduler` para `cpu_id` usando `PriorityPolicy` como
+    /// política padrão.
+    pub fn new(cpu_id: CpuId) -> Self {
+        Self::with_policy(cpu_id, alloc::boxed::Box::new(PriorityPolicy::new()))
+    }
+
+    /// Cria um novo `Scheduler` para `cpu_id` com a política de
+    /// escalonamento informada.
+    pub fn with_policy(cpu_id: CpuId, policy: alloc::boxed::Box<dyn SchedulingPolicy + Send>) -> Self {
         Scheduler {
             tasks: alloc::vec::Vec::new(),
             current_task: 0,
+            policy,
+            cpu_id,
         }
     }
 
-    /// Adiciona uma nova tarefa ao scheduler e cria uma mailbox para ela.
+    /// Adiciona uma nova tarefa à fila local desta CPU e cria uma mailbox,
+    /// com a capacidade padrão, para ela.
+    ///
+    /// Chame isto a partir do próprio `Scheduler` de destino (ex.:
+    /// `current_scheduler().lock().add_task(...)`); para criar uma tarefa
+    /// sem favorecer nenhuma CPU em particular, use `spawn`.
     pub fn add_task(&mut self, task: Task) {
         let task_id = task.id;
         self.tasks.push(task);
-        MAILBOXES.lock().insert(task_id, VecDeque::new());
+        MAILBOXES.lock().insert(task_id, Mailbox::new(DEFAULT_MAILBOX_CAPACITY));
+    }
+
+    /// Tenta repor a fila local quando nenhuma tarefa `Ready` foi encontrada
+    /// nela: primeiro drena um pequeno lote do `INJECTOR`, depois, se ainda
+    /// assim nada entrou, tenta um `try_steal`.
+    ///
+    /// Retorna `true` se ao menos uma tarefa nova entrou na fila local.
+    fn refill(&mut self) -> bool {
+        let mut refilled = false;
+        for _ in 0..INJECTOR_REFILL_BATCH {
+            match INJECTOR.pop() {
+                Some(task) => {
+                    self.tasks.push(task);
+                    refilled = true;
+                }
+                None => break,
+            }
+        }
+
+        if refilled {
+            true
+        } else {
+            self.try_steal()
+        }
+    }
+
+    /// Tenta roubar aproximadamente metade das tarefas `Ready` de outra CPU
+    /// registrada, movendo-as para a fila local.
+    ///
+    /// Nunca rouba a tarefa `current_task` da vítima (ela pode estar
+    /// `Running` naquela CPU agora mesmo) e corrige o índice `current_task`
+    /// da vítima se uma remoção ocorrer antes dele, preservando os
+    /// ponteiros de `TaskContext` que `schedule()` devolve em seguida — a
+    /// `Task` é movida inteira (contexto incluso), nunca realocada no lugar.
+    ///
+    /// Retorna `true` se conseguiu mover ao menos uma tarefa.
+    ///
+    /// Usa `try_lock` em vez de `lock` na vítima: como `schedule()` já roda
+    /// com o `Mutex<Scheduler>` desta CPU travado, duas CPUs tentando roubar
+    /// uma da outra ao mesmo tempo (A trava A e quer B, B trava B e quer A)
+    /// dariam deadlock com um `lock()` bloqueante de um `spin::Mutex` não
+    /// reentrante. Com `try_lock`, uma vítima contendida é simplesmente
+    /// pulada nesta rodada.
+    fn try_steal(&mut self) -> bool {
+        let victim_ids: alloc::vec::Vec<CpuId> = SCHEDULERS
+            .lock()
+            .keys()
+            .copied()
+            .filter(|id| *id != self.cpu_id)
+            .collect();
+
+        for victim_id in victim_ids {
+            let victim = match SCHEDULERS.lock().get(&victim_id).copied() {
+                Some(victim) => victim,
+                None => continue,
+            };
+            let mut victim = match victim.try_lock() {
+                Some(victim) => victim,
+                None => continue,
+            };
+
+            let stealable: alloc::vec::Vec<usize> = victim
+                .tasks
+                .iter()
+                .enumerate()
+                .filter(|(idx, task)| *idx != victim.current_task && task.state == TaskState::Ready)
+                .map(|(idx, _)| idx)
+                .collect();
+
+            let steal_count = stealable.len() / 2;
+            if steal_count == 0 {
+                continue;
+            }
+
+            // Remove de trás para frente para não invalidar os índices já
+            // calculados por `stealable`.
+            for &idx in stealable[stealable.len() - steal_count..].iter().rev() {
+                self.tasks.push(victim.tasks.remove(idx));
+                if idx < victim.current_task {
+                    victim.current_task -= 1;
+                }
+            }
+
+            return true;
+        }
+
+        false
     }
 
-    /// Seleciona a próxima tarefa a ser executada.
+    /// Seleciona a próxima tarefa a ser executada, delegando a decisão à
+    /// política configurada, e realiza a troca de estado entre a tarefa
+    /// atual e a escolhida.
+    ///
+    /// Se a política não encontrar nenhuma tarefa `Ready` na fila local,
+    /// tenta repô-la via `refill` antes de desistir.
     ///
     /// Retorna uma tupla com os contextos da tarefa atual e da próxima tarefa.
     pub fn schedule(&mut self) -> Option<(&mut TaskContext, &TaskContext)> {
         let current_task_index = self.current_task;
-        let mut next_task_index = (current_task_index + 1) % self.tasks.len();
-
-        while self.tasks[next_task_index].state != TaskState::Ready {
-            next_task_index = (next_task_index + 1) % self.tasks.len();
-            if next_task_index == current_task_index {
-                // No other task is ready
-                return None;
+        let next_task_index = match self.policy.pick_next(&self.tasks, current_task_index) {
+            Some(idx) => idx,
+            None => {
+                if !self.refill() {
+                    return None;
+                }
+                self.policy.pick_next(&self.tasks, current_task_index)?
             }
-        }
+        };
 
         self.current_task = next_task_index;
 
@@ -188,24 +624,97 @@ impl Scheduler {
 
 use crate::interrupts::InterruptIndex;
 
-/// Envia uma mensagem para uma tarefa.
+/// Acorda a tarefa receptora se ela estiver bloqueada em `receive`, e
+/// dispara o waker assíncrono registrado via `executor::recv`, se houver.
+fn wake_receiver(receiver_id: TaskId) {
+    if let Some(waker) = MAILBOX_WAKERS.lock().remove(&receiver_id) {
+        waker.wake();
+    }
+
+    with_task(receiver_id, |task| {
+        // Zera o deadline para que um `receive_timeout` pendente saiba que
+        // foi uma mensagem, e não um timeout, que o acordou.
+        task.wake_deadline = None;
+        if task.state == TaskState::Blocked {
+            task.state = TaskState::Ready;
+        }
+    });
+}
+
+/// Acorda, se houver, o remetente bloqueado há mais tempo esperando espaço
+/// na mailbox de `task_id`. Chamado após `receive`/`try_receive` drenarem
+/// uma entrada, para implementar a contrapressão de `send`.
+fn wake_one_blocked_sender(task_id: TaskId) {
+    let sender_id = match MAILBOXES.lock().get_mut(&task_id) {
+        Some(mailbox) => mailbox.blocked_senders.pop_front(),
+        None => None,
+    };
+
+    let Some(sender_id) = sender_id else { return };
+    with_task(sender_id, |task| {
+        if task.state == TaskState::Blocked {
+            task.state = TaskState::Ready;
+        }
+    });
+}
+
+/// Tenta enviar `message` para `receiver_id` sem bloquear.
 ///
-/// Se a tarefa receptora estiver bloqueada, ela é acordada.
-/// Retorna `true` se a mensagem foi enviada com sucesso.
-pub fn send(receiver_id: TaskId, message: Message) -> bool {
+/// Retorna `WouldBlock` em vez de esperar se a mailbox estiver cheia, e
+/// `NoSuchTask` se não existir mailbox para `receiver_id`; em ambos os
+/// casos de falha, `message` volta no segundo elemento da tupla em vez de
+/// ser consumida, para que o chamador (ex. `send`) possa tentar de novo sem
+/// precisar clonar.
+pub fn try_send(receiver_id: TaskId, message: Message) -> (SendResult, Option<Message>) {
     let mut mailboxes = MAILBOXES.lock();
-    if let Some(mailbox) = mailboxes.get_mut(&receiver_id) {
-        mailbox.push_back(message);
-        // Wake up the receiver if it was blocked
-        let mut scheduler = crate::SCHEDULER.lock();
-        if let Some(task) = scheduler.tasks.iter_mut().find(|t| t.id == receiver_id) {
-            if task.state == TaskState::Blocked {
-                task.state = TaskState::Ready;
-            }
+    let mailbox = match mailboxes.get_mut(&receiver_id) {
+        Some(mailbox) => mailbox,
+        None => return (SendResult::NoSuchTask, Some(message)),
+    };
+
+    if mailbox.queue.len() >= mailbox.capacity {
+        return (SendResult::WouldBlock, Some(message));
+    }
+
+    mailbox.queue.push_back(message);
+    drop(mailboxes);
+    wake_receiver(receiver_id);
+    (SendResult::Sent, None)
+}
+
+/// Envia `message` para `receiver_id`, bloqueando a tarefa atual enquanto a
+/// mailbox estiver cheia em vez de falhar.
+///
+/// A cada tentativa, o registro em `blocked_senders` e a transição para
+/// `Blocked` acontecem sem soltar o lock de `MAILBOXES` entre as duas
+/// operações: se fossem dois passos separados, `wake_one_blocked_sender`
+/// poderia desenfileirar esta tarefa entre eles (ainda `Running`) sem nunca
+/// marcá-la `Ready`, prendendo-a bloqueada para sempre fora da fila. Por
+/// isso também não há um `registered` que sobrevive entre iterações: cada
+/// re-bloqueio volta a se registrar do zero. Retorna `NoSuchTask` se não
+/// existir mailbox para `receiver_id`; caso contrário sempre retorna `Sent`.
+pub fn send(receiver_id: TaskId, message: Message) -> SendResult {
+    let mut message = message;
+    loop {
+        let mut mailboxes = MAILBOXES.lock();
+        let mailbox = match mailboxes.get_mut(&receiver_id) {
+            Some(mailbox) => mailbox,
+            None => return SendResult::NoSuchTask,
+        };
+
+        if mailbox.queue.len() < mailbox.capacity {
+            mailbox.queue.push_back(message);
+            drop(mailboxes);
+            wake_receiver(receiver_id);
+            return SendResult::Sent;
         }
-        true
-    } else {
-        false
+
+        let my_id = current_scheduler().lock().current_task_id();
+        mailbox.blocked_senders.push_back(my_id);
+        with_task(my_id, |task| task.state = TaskState::Blocked);
+        drop(mailboxes);
+
+        yield_now();
     }
 }
 
@@ -213,26 +722,116 @@ pub fn send(receiver_id: TaskId, message: Message) -> bool {
 ///
 /// Se a mailbox estiver vazia, a tarefa é bloqueada até que uma mensagem chegue.
 pub fn receive() -> Message {
-    let my_id = crate::SCHEDULER.lock().current_task_id();
+    let my_id = current_scheduler().lock().current_task_id();
     loop {
-        let mut mailboxes = MAILBOXES.lock();
-        if let Some(msg) = mailboxes.get_mut(&my_id).unwrap().pop_front() {
-            return msg;
+        if let Some(message) = try_receive_for(my_id) {
+            return message;
         }
-        drop(mailboxes);
 
         // Block the task and yield
         {
-            let mut scheduler = crate::SCHEDULER.lock();
+            let mut scheduler = current_scheduler().lock();
+            let current_task = scheduler.tasks.iter_mut().find(|t| t.id == my_id).unwrap();
+            current_task.state = TaskState::Blocked;
+        }
+        unsafe {
+            core::arch::asm!("int {}", const InterruptIndex::Yield as u8);
+        }
+    }
+}
+
+/// Variante não bloqueante de `receive`: retorna `None` imediatamente se a
+/// mailbox da tarefa atual estiver vazia.
+pub fn try_receive() -> Option<Message> {
+    let my_id = current_scheduler().lock().current_task_id();
+    try_receive_for(my_id)
+}
+
+/// Bloqueia a tarefa atual por `ticks` interrupções de timer.
+///
+/// Registra o despertar na `TIMER_WHEEL` e permanece `Blocked` até que
+/// `advance_clock` zere seu `wake_deadline`. Uma mensagem que chegue antes
+/// (`wake_receiver` também zera `wake_deadline`) não encerra a espera.
+pub fn sleep(ticks: u64) {
+    let my_id = current_scheduler().lock().current_task_id();
+    let wake_tick = current_tick().saturating_add(ticks);
+    register_wake(my_id, wake_tick);
+
+    loop {
+        {
+            let mut scheduler = current_scheduler().lock();
+            if current_tick() >= wake_tick {
+                return;
+            }
             let current_task = scheduler.tasks.iter_mut().find(|t| t.id == my_id).unwrap();
+            // Restaura o deadline caso uma mensagem o tenha zerado; a entrada
+            // em `TIMER_WHEEL` continua pendente até `wake_tick`.
+            current_task.wake_deadline = Some(wake_tick);
             current_task.state = TaskState::Blocked;
         }
         unsafe {
-            core::arch::asm!("int {}", const InterruptIndex::Timer as u8);
+            core::arch::asm!("int {}", const InterruptIndex::Yield as u8);
         }
     }
 }
 
+/// Variante de `receive` com prazo: espera por uma mensagem por no máximo
+/// `ticks` interrupções de timer, devolvendo `None` se o prazo vencer antes
+/// de uma mensagem chegar.
+///
+/// Distingue as duas razões de acordar através de `Task::wake_deadline`: uma
+/// mensagem o zera (aqui, ou em `wake_receiver`, quando `send` acorda esta
+/// tarefa), `advance_clock` o zera quando é o timeout.
+pub fn receive_timeout(ticks: u64) -> Option<Message> {
+    let my_id = current_scheduler().lock().current_task_id();
+    let wake_tick = current_tick().saturating_add(ticks);
+    register_wake(my_id, wake_tick);
+
+    loop {
+        if let Some(message) = try_receive_for(my_id) {
+            with_task(my_id, |task| task.wake_deadline = None);
+            return Some(message);
+        }
+
+        let timed_out = current_scheduler()
+            .lock()
+            .tasks
+            .iter()
+            .find(|t| t.id == my_id)
+            .map(|t| t.wake_deadline.is_none())
+            .unwrap_or(true);
+        if timed_out {
+            return None;
+        }
+
+        {
+            let mut scheduler = current_scheduler().lock();
+            let current_task = scheduler.tasks.iter_mut().find(|t| t.id == my_id).unwrap();
+            current_task.state = TaskState::Blocked;
+        }
+        unsafe {
+            core::arch::asm!("int {}", const InterruptIndex::Yield as u8);
+        }
+    }
+}
+
+/// Implementação comum de `receive`/`try_receive`/`executor::Receive`:
+/// drena uma mensagem da mailbox de `task_id`, se houver, acordando em
+/// seguida o remetente bloqueado há mais tempo.
+pub(crate) fn try_receive_for(task_id: TaskId) -> Option<Message> {
+    let message = MAILBOXES.lock().get_mut(&task_id)?.queue.pop_front();
+    if message.is_some() {
+        wake_one_blocked_sender(task_id);
+    }
+    message
+}
+
+/// Registra o `Waker` a ser chamado assim que a próxima mensagem chegar na
+/// mailbox de `task_id`, substituindo qualquer waker previamente registrado.
+pub(crate) fn register_mailbox_waker(task_id: TaskId, waker: Waker) {
+    MAILBOX_WAKERS.lock().insert(task_id, waker);
+}
+
 impl Scheduler {
     /// Retorna o ID da tarefa atual.
     pub fn current_task_id(&self) -> TaskId {
@@ -243,6 +842,6 @@ impl Scheduler {
 /// Cede o tempo de CPU da tarefa atual para o scheduler.
 pub fn yield_now() {
     unsafe {
-        core::arch::asm!("int {}", const InterruptIndex::Timer as u8);
+        core::arch::asm!("int {}", const InterruptIndex::Yield as u8);
     }
 }
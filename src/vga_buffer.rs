@@ -22,6 +22,28 @@ pub fn disable_cursor() {
     }
 }
 
+/// Reabilita e posiciona o cursor de hardware do VGA na posição `(row, col)`.
+///
+/// É o inverso de `disable_cursor`: limpa o bit de desabilitação no
+/// registrador 0x0A e programa a posição nos registradores 0x0E/0x0F (parte
+/// alta/baixa do índice linear `row * BUFFER_WIDTH + col`).
+fn enable_and_position_cursor(row: usize, col: usize) {
+    let position = (row * BUFFER_WIDTH + col) as u16;
+    unsafe {
+        let mut port_3d4 = Port::new(0x3D4);
+        let mut port_3d5: Port<u8> = Port::new(0x3D5);
+
+        port_3d4.write(0x0Au8);
+        let val: u8 = port_3d5.read();
+        port_3d5.write(val & !0x20);
+
+        port_3d4.write(0x0Eu8);
+        port_3d5.write((position >> 8) as u8);
+        port_3d4.write(0x0Fu8);
+        port_3d5.write((position & 0xFF) as u8);
+    }
+}
+
 /// Enum para as cores padrão do modo de texto VGA.
 #[allow(dead_code)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -45,6 +67,24 @@ pub enum Color {
     White = 15,
 }
 
+impl Color {
+    /// Converte o dígito de cor de primeiro plano de uma sequência SGR ANSI
+    /// (`\x1b[3Xm`, X em 0..=7) para a cor VGA correspondente mais próxima.
+    fn from_ansi_digit(digit: u16) -> Option<Color> {
+        Some(match digit {
+            0 => Color::Black,
+            1 => Color::Red,
+            2 => Color::Green,
+            3 => Color::Brown,
+            4 => Color::Blue,
+            5 => Color::Magenta,
+            6 => Color::Cyan,
+            7 => Color::LightGray,
+            _ => return None,
+        })
+    }
+}
+
 /// Representa um código de cor completo, incluindo cor de primeiro plano e de fundo.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(transparent)]
@@ -76,57 +116,178 @@ struct Buffer {
     chars: [[Volatile<ScreenChar>; BUFFER_WIDTH]; BUFFER_HEIGHT],
 }
 
+/// Estado do pequeno parser de escapes ANSI embutido em `write_string`.
+///
+/// Só reconhece o subconjunto usado pelo shell e pela tela de pânico:
+/// `\x1b[2J` (limpar tela), `\x1b[<row>;<col>H` (posicionar cursor) e
+/// `\x1b[3Xm` (cor de primeiro plano). Qualquer outra sequência CSI é
+/// consumida e silenciosamente ignorada.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AnsiState {
+    /// Nenhum escape em andamento; bytes são escritos normalmente.
+    Ground,
+    /// Acabamos de ver `ESC` (0x1b) e esperamos `[`.
+    Escape,
+    /// Dentro de uma sequência CSI, acumulando parâmetros numéricos.
+    Csi,
+}
+
 /// Um `Writer` que permite escrever no buffer de texto VGA.
 ///
-/// Mantém o controle da posição atual do cursor e da cor do texto.
+/// Mantém um cursor explícito `(row, col)`, uma cor de primeiro/segundo plano
+/// configurável e interpreta um subconjunto mínimo de escapes ANSI.
 pub struct Writer {
-    column_position: usize,
+    cursor_row: usize,
+    cursor_col: usize,
     color_code: ColorCode,
     buffer: &'static mut Buffer,
+    ansi_state: AnsiState,
+    /// Parâmetros numéricos acumulados da sequência CSI atual (até 4).
+    csi_params: [u16; 4],
+    /// Índice do parâmetro atual dentro de `csi_params`.
+    csi_param_index: usize,
 }
 
 impl Writer {
-    /// Escreve um único byte ASCII na tela.
+    /// Escreve um único byte ASCII na tela, fora de qualquer sequência ANSI.
     ///
-    /// Caracteres de nova linha (`\n`) são tratados especialmente.
+    /// Caracteres de nova linha (`\n`) e backspace (0x08) são tratados
+    /// especialmente.
     pub fn write_byte(&mut self, byte: u8) {
         match byte {
             b'\n' => self.new_line(),
+            0x08 => self.move_cursor_back(),
             byte => {
-                if self.column_position >= BUFFER_WIDTH {
+                if self.cursor_col >= BUFFER_WIDTH {
                     self.new_line();
                 }
 
-                let row = BUFFER_HEIGHT - 1;
-                let col = self.column_position;
-
+                let row = self.cursor_row;
+                let col = self.cursor_col;
                 let color_code = self.color_code;
                 self.buffer.chars[row][col].write(ScreenChar {
                     ascii_character: byte,
                     color_code,
                 });
-                self.column_position += 1;
+                self.cursor_col += 1;
+                self.sync_hardware_cursor();
             }
         }
     }
 
-    /// Escreve a string fornecida na tela.
+    /// Escreve a string fornecida na tela, interpretando sequências de
+    /// escape ANSI reconhecidas (veja `AnsiState`) em vez de imprimi-las.
     ///
-    /// Caracteres que não são ASCII imprimíveis (na faixa de 0x20 a 0x7e)
-    /// são impressos como `■`.
+    /// Caracteres que não são ASCII imprimíveis (na faixa de 0x20 a 0x7e, fora
+    /// de uma sequência ANSI) são impressos como `0xfe` (`■` na fonte VGA).
     pub fn write_string(&mut self, s: &str) {
         for byte in s.bytes() {
-            match byte {
-                // printable ASCII byte or newline
-                0x20..=0x7e | b'\n' => self.write_byte(byte),
-                // not part of printable ASCII range
+            self.write_ansi_byte(byte);
+        }
+    }
+
+    /// Processa um único byte através da máquina de estados de escapes ANSI.
+    fn write_ansi_byte(&mut self, byte: u8) {
+        match self.ansi_state {
+            AnsiState::Ground => match byte {
+                0x1b => self.ansi_state = AnsiState::Escape,
+                0x20..=0x7e | b'\n' | 0x08 => self.write_byte(byte),
                 _ => self.write_byte(0xfe),
+            },
+            AnsiState::Escape => match byte {
+                b'[' => {
+                    self.csi_params = [0; 4];
+                    self.csi_param_index = 0;
+                    self.ansi_state = AnsiState::Csi;
+                }
+                // Escape desconhecido: volta ao estado normal e descarta o byte.
+                _ => self.ansi_state = AnsiState::Ground,
+            },
+            AnsiState::Csi => match byte {
+                b'0'..=b'9' => {
+                    let digit = (byte - b'0') as u16;
+                    let param = &mut self.csi_params[self.csi_param_index];
+                    *param = param.saturating_mul(10).saturating_add(digit);
+                }
+                b';' => {
+                    self.csi_param_index = (self.csi_param_index + 1).min(self.csi_params.len() - 1);
+                }
+                final_byte => {
+                    self.execute_csi(final_byte);
+                    self.ansi_state = AnsiState::Ground;
+                }
+            },
+        }
+    }
+
+    /// Executa a ação correspondente ao byte final de uma sequência CSI já
+    /// com os parâmetros acumulados em `csi_params`.
+    fn execute_csi(&mut self, final_byte: u8) {
+        let params = self.csi_params;
+        match final_byte {
+            // `\x1b[2J`: limpa a tela inteira.
+            b'J' if params[0] == 2 => self.clear_screen(),
+            // `\x1b[<row>;<col>H`: move o cursor (1-indexado, como no padrão ANSI).
+            b'H' => {
+                let row = params[0].max(1) as usize - 1;
+                let col = params[1].max(1) as usize - 1;
+                self.set_cursor_position(row, col);
             }
+            // `\x1b[3Xm`: define a cor de primeiro plano, mantendo o fundo atual.
+            b'm' => {
+                if params[0] >= 30 && params[0] <= 37 {
+                    if let Some(fg) = Color::from_ansi_digit(params[0] - 30) {
+                        let background = self.color_code.0 >> 4;
+                        self.color_code = ColorCode((background << 4) | fg as u8);
+                    }
+                }
+            }
+            _ => {}
         }
     }
 
-    /// Move o cursor para uma nova linha, rolando a tela se necessário.
+    /// Posiciona o cursor em `(row, col)`, fixando os valores dentro dos
+    /// limites do buffer.
+    pub fn set_cursor_position(&mut self, row: usize, col: usize) {
+        self.cursor_row = row.min(BUFFER_HEIGHT - 1);
+        self.cursor_col = col.min(BUFFER_WIDTH - 1);
+        self.sync_hardware_cursor();
+    }
+
+    /// Define a cor de primeiro plano e de fundo usada pelas próximas escritas.
+    pub fn set_color(&mut self, foreground: Color, background: Color) {
+        self.color_code = ColorCode::new(foreground, background);
+    }
+
+    /// Move o cursor de hardware do VGA para acompanhar a posição lógica
+    /// atual do `Writer`.
+    fn sync_hardware_cursor(&self) {
+        enable_and_position_cursor(self.cursor_row, self.cursor_col);
+    }
+
+    /// Move o cursor lógico uma coluna para trás, sem apagar o conteúdo
+    /// (usado pelo shell, que sobrescreve a célula com um espaço em seguida).
+    fn move_cursor_back(&mut self) {
+        if self.cursor_col > 0 {
+            self.cursor_col -= 1;
+        }
+        self.sync_hardware_cursor();
+    }
+
+    /// Avança o cursor para o início da próxima linha, rolando a tela apenas
+    /// quando o cursor já está na última linha.
     fn new_line(&mut self) {
+        if self.cursor_row + 1 < BUFFER_HEIGHT {
+            self.cursor_row += 1;
+        } else {
+            self.scroll_up();
+        }
+        self.cursor_col = 0;
+        self.sync_hardware_cursor();
+    }
+
+    /// Rola toda a tela uma linha para cima, descartando a linha do topo.
+    fn scroll_up(&mut self) {
         for row in 1..BUFFER_HEIGHT {
             for col in 0..BUFFER_WIDTH {
                 let character = self.buffer.chars[row][col].read();
@@ -134,7 +295,6 @@ impl Writer {
             }
         }
         self.clear_row(BUFFER_HEIGHT - 1);
-        self.column_position = 0;
     }
 
     /// Limpa uma linha, preenchendo-a com espaços em branco.
@@ -148,11 +308,12 @@ impl Writer {
         }
     }
 
-    /// Limpa a tela inteira.
+    /// Limpa a tela inteira e reposiciona o cursor no canto superior esquerdo.
     pub fn clear_screen(&mut self) {
         for row in 0..BUFFER_HEIGHT {
             self.clear_row(row);
         }
+        self.set_cursor_position(0, 0);
     }
 }
 
@@ -168,9 +329,13 @@ lazy_static! {
     ///
     /// É protegido por um `Mutex` para garantir que seja seguro para threads.
     pub static ref WRITER: Mutex<Writer> = Mutex::new(Writer {
-        column_position: 0,
+        cursor_row: BUFFER_HEIGHT - 1,
+        cursor_col: 0,
         color_code: ColorCode::new(Color::Yellow, Color::Black),
         buffer: unsafe { &mut *(0xb8000 as *mut Buffer) },
+        ansi_state: AnsiState::Ground,
+        csi_params: [0; 4],
+        csi_param_index: 0,
     });
 }
 
@@ -188,10 +353,14 @@ macro_rules! println {
 }
 
 /// Função auxiliar privada usada pelas macros `print!` e `println!`.
+///
+/// Espelha a saída na porta serial, para que logs do kernel também fiquem
+/// disponíveis no terminal do host ao rodar com `-serial stdio`.
 #[doc(hidden)]
 pub fn _print(args: fmt::Arguments) {
     use core::fmt::Write;
     WRITER.lock().write_fmt(args).unwrap();
+    crate::serial::_print(args);
 }
 
 /// Macro para limpar a tela.
@@ -205,3 +374,19 @@ macro_rules! clear_screen {
 pub fn _clear_screen() {
     WRITER.lock().clear_screen();
 }
+
+/// Pinta a tela inteira de vermelho e imprime a mensagem de pânico em uma
+/// posição fixa, para um diagnóstico legível mesmo quando o restante do
+/// estado do kernel está comprometido.
+///
+/// Usado pelo handler de pânico em vez de `println!`: recoloca o cursor e a
+/// cor do `WRITER` global diretamente, sem depender de nenhuma sequência ANSI.
+pub fn panic_screen(message: fmt::Arguments) {
+    use core::fmt::Write;
+
+    let mut writer = WRITER.lock();
+    writer.set_color(Color::White, Color::Red);
+    writer.clear_screen();
+    writer.set_cursor_position(0, 0);
+    let _ = writer.write_fmt(message);
+}